@@ -1,4 +1,5 @@
-use salesforce_core::client::{self, AuthFlow, Credentials};
+use salesforce_core::client::{self, AuthFlow};
+use salesforce_core::http::HttpConfig;
 use salesforce_core::pubsub::context::Context;
 use salesforce_pubsub_v1::eventbus;
 use std::env;
@@ -17,59 +18,39 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .await?;
 
     // Example 2: Initialize client with Client Credentials flow (environment variables)
+    // Reads SALESFORCE_CLIENT_ID, SALESFORCE_CLIENT_SECRET, SALESFORCE_INSTANCE_URL
+    // and the optional SALESFORCE_TENANT_ID
     let _client = client::Builder::new()
-        .credentials(Credentials {
-            client_id: env::var("SALESFORCE_CLIENT_ID")
-                .expect("SALESFORCE_CLIENT_ID environment variable not set"),
-            client_secret: Some(
-                env::var("SALESFORCE_CLIENT_SECRET")
-                    .expect("SALESFORCE_CLIENT_SECRET environment variable not set"),
-            ),
-            username: None,
-            password: None,
-            instance_url: env::var("SALESFORCE_INSTANCE_URL")
-                .unwrap_or_else(|_| "https://mysalesforce.my.salesforce.com".to_string()),
-            tenant_id: env::var("SALESFORCE_TENANT_ID")
-                .expect("SALESFORCE_TENANT_ID environment variable not set"),
-        })
+        .credentials_env("SALESFORCE")
         .auth_flow(AuthFlow::ClientCredentials)
         .build()?
         .connect()
         .await?;
 
     // Example 3: Initialize client with Username-Password flow
-    let client = client::Builder::new()
-        .credentials(Credentials {
-            client_id: env::var("SALESFORCE_CLIENT_ID")
-                .expect("SALESFORCE_CLIENT_ID environment variable not set"),
-            client_secret: Some(
-                env::var("SALESFORCE_CLIENT_SECRET")
-                    .expect("SALESFORCE_CLIENT_SECRET environment variable not set"),
-            ),
-            username: Some(
-                env::var("SALESFORCE_USERNAME")
-                    .expect("SALESFORCE_USERNAME environment variable not set"),
-            ),
-            password: Some(
-                env::var("SALESFORCE_PASSWORD")
-                    .expect("SALESFORCE_PASSWORD environment variable not set"),
-            ),
-            instance_url: env::var("SALESFORCE_INSTANCE_URL")
-                .unwrap_or_else(|_| "https://mysalesforce.my.salesforce.com".to_string()),
-            tenant_id: env::var("SALESFORCE_TENANT_ID")
-                .expect("SALESFORCE_TENANT_ID environment variable not set"),
-        })
+    // Additionally reads SALESFORCE_USERNAME and SALESFORCE_PASSWORD
+    let _client = client::Builder::new()
+        .credentials_env("SALESFORCE")
         .auth_flow(AuthFlow::UsernamePassword)
         .build()?
         .connect()
         .await?;
 
+    // Example 4: Reuse a Salesforce CLI login
+    // The URL is printed by `sf org display --verbose` as "Sfdx Auth Url"
+    let client = client::Builder::new()
+        .sfdx_auth_url(env::var("SFDX_AUTH_URL")?)
+        .build()?
+        .connect()
+        .await?;
+
     info!("Client connected successfully");
 
     // Connect to Pub/Sub API
-    let channel = tonic::transport::Channel::from_static(eventbus::ENDPOINT)
-        .connect()
-        .await?;
+    // Set a proxy, CA certificates or timeouts here to apply them to both
+    // the OAuth2 client (via `Builder::http_config`) and the gRPC channel
+    let http_config = HttpConfig::default();
+    let channel = http_config.connect_channel(eventbus::ENDPOINT).await?;
 
     let mut context = Context::new(channel, client)?;
 
@@ -118,12 +99,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     match context.subscribe(fetch_request).await {
-        Ok(response) => {
+        Ok(mut stream) => {
             info!("Subscribed to topic successfully");
-            let stream = response.into_inner();
 
             tokio::spawn(async move {
-                let mut stream = stream;
                 while let Some(result) = stream.next().await {
                     match result {
                         Ok(fetch_response) => {
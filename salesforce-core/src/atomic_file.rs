@@ -0,0 +1,83 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counter that keeps temporary file names unique within a process.
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Replaces the file at `path` with `contents`.
+///
+/// The contents are written and synced to a temporary file next to `path`,
+/// which is then renamed over it, so readers and crashes never observe a
+/// partially written file.
+pub(crate) fn write(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    write_with(path, contents, false)
+}
+
+/// Like [`write`], but the file is readable only by the current user.
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    write_with(path, contents, true)
+}
+
+fn write_with(path: &Path, contents: &[u8], private: bool) -> std::io::Result<()> {
+    let temp_path = temp_path(path);
+    let result = create(&temp_path, private)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Creates a new file at `path`, failing if it already exists.
+fn create(path: &Path, private: bool) -> std::io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    options.open(path)
+}
+
+/// Returns a temporary path next to `path` that is unique across threads
+/// and processes.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_replaces_file_without_leftovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        write(&path, b"first").unwrap();
+        write(&path, b"second").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_temp_paths_are_unique() {
+        let path = Path::new("/tmp/state.json");
+        assert_ne!(temp_path(path), temp_path(path));
+    }
+}
@@ -12,11 +12,12 @@
 //! let client = client::Builder::new()
 //!     .credentials(Credentials {
 //!         client_id: "...".to_string(),
-//!         client_secret: Some("...".to_string()),
+//!         client_secret: Some("...".into()),
 //!         username: None,
 //!         password: None,
 //!         instance_url: "https://your-instance.salesforce.com".to_string(),
-//!         tenant_id: "...".to_string(),
+//!         tenant_id: Some("...".to_string()),
+//!         ..Default::default()
 //!     })
 //!     .build()?
 //!     .connect()
@@ -25,11 +26,59 @@
 //! # }
 //! ```
 
+/// Crash-safe file replacement shared by the file-backed stores.
+pub(crate) mod atomic_file;
+
 /// OAuth2 client authentication and connection management.
 pub mod client;
 
+/// Network configuration shared by the HTTP client and gRPC channels.
+pub mod http;
+
+/// Named connection profiles for working with multiple orgs.
+pub mod registry;
+
+/// Wrapper types that keep secrets out of logs.
+pub mod secret;
+
+/// Access token lifecycle management.
+pub mod token {
+    /// Shared access token with automatic refresh before expiry.
+    pub mod provider;
+    /// Token caching across clients and processes.
+    pub mod store;
+}
+
 /// Salesforce Pub/Sub API for real-time event streaming.
 pub mod pubsub {
+    /// Avro schema parsing and payload encoding and decoding.
+    pub mod avro;
+    /// Change Data Capture event headers and changed field resolution.
+    pub mod cdc;
     /// Pub/Sub context for managing gRPC connections and operations.
     pub mod context;
+    /// Typed classification of failed Pub/Sub gRPC calls.
+    pub mod error;
+    /// Credit-based flow control for Subscribe streams.
+    pub mod flow;
+    /// Random IDs for events and commit requests.
+    pub(crate) mod ids;
+    /// Managed subscriptions with server-side replay ID commits.
+    pub mod managed;
+    /// Bidirectional PublishStream handle.
+    pub mod publish_stream;
+    /// Batching publisher with per-event results.
+    pub mod publisher;
+    /// Checkpoint storage for the replay IDs of processed events.
+    pub mod replay_store;
+    /// Backoff and retry limits for recovering from transient failures.
+    pub mod retry;
+    /// Schema lookup and caching by schema ID.
+    pub mod schema_cache;
+    /// Subscriptions that resume from the last replay ID after failures.
+    pub mod subscriber;
+    #[cfg(test)]
+    pub(crate) mod testing;
+    /// Publishing and consuming events as Rust types via serde.
+    pub mod typed;
 }
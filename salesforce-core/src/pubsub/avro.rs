@@ -0,0 +1,1619 @@
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+/// Largest number of items accepted in one block of an array whose items
+/// take no bytes on the wire, such as an array of `null`.
+const MAX_ZERO_WIDTH_ITEMS: u64 = 1 << 20;
+
+/// Errors that can occur while parsing schemas or encoding and decoding payloads.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Schema is not valid JSON.
+    #[error("Failed to parse schema JSON: {source}")]
+    ParseSchema {
+        #[source]
+        source: serde_json::Error,
+    },
+    /// Schema is valid JSON but not a valid Avro schema.
+    #[error("Invalid Avro schema: {message}")]
+    InvalidSchema {
+        /// Description of the problem.
+        message: String,
+    },
+    /// Payload does not match the schema.
+    #[error("Failed to decode Avro payload: {message}")]
+    Decode {
+        /// Description of the problem.
+        message: String,
+    },
+    /// Value does not match the schema.
+    #[error("Failed to encode Avro value: {message}")]
+    Encode {
+        /// Description of the problem, prefixed with the path of the field.
+        message: String,
+    },
+}
+
+/// Node of a parsed Avro schema.
+///
+/// Logical type annotations such as `timestamp-millis` are ignored, so those
+/// fields are represented by their underlying type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    /// `null`
+    Null,
+    /// `boolean`
+    Boolean,
+    /// `int`, a 32-bit signed integer.
+    Int,
+    /// `long`, a 64-bit signed integer.
+    Long,
+    /// `float`, a 32-bit IEEE 754 number.
+    Float,
+    /// `double`, a 64-bit IEEE 754 number.
+    Double,
+    /// `bytes`
+    Bytes,
+    /// `string`
+    String,
+    /// Array with the given item schema.
+    Array(Box<Node>),
+    /// Map from strings to the given value schema.
+    Map(Box<Node>),
+    /// Union of the given branches.
+    Union(Vec<Node>),
+    /// Named record.
+    Record(Arc<Record>),
+    /// Named enum.
+    Enum(Arc<Enum>),
+    /// Named fixed-size byte sequence.
+    Fixed(Arc<Fixed>),
+    /// Reference to a named type from inside its own definition.
+    Ref(String),
+}
+
+/// Record schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// Full name including the namespace, e.g. `com.sforce.eventbus.ChangeEventHeader`.
+    pub name: String,
+    /// Documentation string.
+    pub doc: Option<String>,
+    /// Fields in declaration order, which is also their order on the wire.
+    pub fields: Vec<Field>,
+}
+
+impl Record {
+    /// Returns the field named `name`.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+/// Field of a record schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    /// Field name.
+    pub name: String,
+    /// Documentation string. Salesforce uses it for the field's data type.
+    pub doc: Option<String>,
+    /// Field schema.
+    pub schema: Node,
+    /// Default value used when encoding a record without this field.
+    pub default: Option<JsonValue>,
+}
+
+/// Enum schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enum {
+    /// Full name including the namespace.
+    pub name: String,
+    /// Symbols in declaration order.
+    pub symbols: Vec<String>,
+}
+
+/// Fixed schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fixed {
+    /// Full name including the namespace.
+    pub name: String,
+    /// Size in bytes.
+    pub size: usize,
+}
+
+/// Dynamically typed Avro value.
+///
+/// Unions have no variant of their own: a decoded union is the value of the
+/// branch that was written, and encoding picks the first branch the value
+/// fits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// `null`
+    Null,
+    /// `boolean`
+    Boolean(bool),
+    /// `int`
+    Int(i32),
+    /// `long`
+    Long(i64),
+    /// `float`
+    Float(f32),
+    /// `double`
+    Double(f64),
+    /// `bytes`
+    Bytes(Vec<u8>),
+    /// `string`
+    String(String),
+    /// Array items.
+    Array(Vec<Value>),
+    /// Map entries.
+    Map(BTreeMap<String, Value>),
+    /// Record fields in schema order.
+    Record(Vec<(String, Value)>),
+    /// Enum symbol.
+    Enum(String),
+    /// Fixed bytes.
+    Fixed(Vec<u8>),
+}
+
+impl Value {
+    /// Returns the record field named `name`, or `None` if this is not a record.
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Record(fields) => fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Returns the string or enum symbol.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) | Value::Enum(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the `int` or `long` value.
+    pub fn as_long(&self) -> Option<i64> {
+        match self {
+            Value::Int(n) => Some(i64::from(*n)),
+            Value::Long(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Returns the array items.
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the value's type, used in error messages.
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Boolean(_) => "boolean",
+            Value::Int(_) => "int",
+            Value::Long(_) => "long",
+            Value::Float(_) => "float",
+            Value::Double(_) => "double",
+            Value::Bytes(_) => "bytes",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Record(_) => "record",
+            Value::Enum(_) => "enum",
+            Value::Fixed(_) => "fixed",
+        }
+    }
+}
+
+impl From<Value> for JsonValue {
+    /// Converts a value into JSON: records and maps become objects, enums
+    /// strings, and bytes arrays of numbers. Non-finite numbers become `null`.
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => JsonValue::Null,
+            Value::Boolean(b) => JsonValue::Bool(b),
+            Value::Int(n) => JsonValue::from(n),
+            Value::Long(n) => JsonValue::from(n),
+            Value::Float(n) => JsonValue::from(n),
+            Value::Double(n) => JsonValue::from(n),
+            Value::Bytes(bytes) | Value::Fixed(bytes) => JsonValue::from(bytes),
+            Value::String(s) | Value::Enum(s) => JsonValue::String(s),
+            Value::Array(items) => items.into_iter().map(JsonValue::from).collect(),
+            Value::Map(entries) => entries
+                .into_iter()
+                .map(|(key, value)| (key, JsonValue::from(value)))
+                .collect(),
+            Value::Record(fields) => fields
+                .into_iter()
+                .map(|(name, value)| (name, JsonValue::from(value)))
+                .collect(),
+        }
+    }
+}
+
+/// Parsed Avro schema, as returned in `SchemaInfo.schema_json`.
+///
+/// # Examples
+///
+/// ```
+/// use salesforce_core::pubsub::avro::{Schema, Value};
+///
+/// let schema = Schema::parse(
+///     r#"{
+///         "type": "record",
+///         "name": "Order_Placed__e",
+///         "namespace": "com.sforce.eventbus",
+///         "fields": [
+///             {"name": "CreatedDate", "type": "long"},
+///             {"name": "Order_Number__c", "type": ["null", "string"], "default": null}
+///         ]
+///     }"#,
+/// )?;
+///
+/// let payload = schema.encode(&Value::Record(vec![
+///     ("CreatedDate".to_string(), Value::Long(1_700_000_000_000)),
+///     ("Order_Number__c".to_string(), Value::String("ORD-1001".to_string())),
+/// ]))?;
+///
+/// let event = schema.decode(&payload)?;
+/// assert_eq!(event.field("Order_Number__c").and_then(Value::as_str), Some("ORD-1001"));
+/// # Ok::<(), salesforce_core::pubsub::avro::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    root: Node,
+    names: HashMap<String, Node>,
+}
+
+impl Schema {
+    /// Parses a schema from its JSON representation.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ParseSchema`] if the input is not JSON, or
+    /// [`Error::InvalidSchema`] if it is not a valid Avro schema.
+    pub fn parse(schema_json: &str) -> Result<Self, Error> {
+        let json: JsonValue =
+            serde_json::from_str(schema_json).map_err(|e| Error::ParseSchema { source: e })?;
+        let mut parser = Parser::default();
+        let root = parser.parse(&json, None)?;
+        Ok(Self {
+            root,
+            names: parser.names,
+        })
+    }
+
+    /// Returns the root node.
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    /// Returns the named type with the given full name.
+    pub fn named(&self, name: &str) -> Option<&Node> {
+        self.names.get(name)
+    }
+
+    /// Follows [`Node::Ref`] to the named type it refers to.
+    pub fn resolve<'a>(&'a self, node: &'a Node) -> &'a Node {
+        match node {
+            Node::Ref(name) => self.names.get(name).unwrap_or(node),
+            node => node,
+        }
+    }
+
+    /// Decodes an event payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Decode`] if the payload is truncated, malformed or
+    /// longer than the schema describes.
+    pub fn decode(&self, payload: &[u8]) -> Result<Value, Error> {
+        let mut reader = Reader { buf: payload };
+        let value = self.decode_node(&self.root, &mut reader)?;
+        if !reader.buf.is_empty() {
+            return Err(decode_error(format!(
+                "{} trailing bytes after value",
+                reader.buf.len()
+            )));
+        }
+        Ok(value)
+    }
+
+    /// Encodes a value into an event payload.
+    ///
+    /// Record fields missing from the value are filled from the schema
+    /// default. Numeric values are promoted where Avro allows it, e.g. an
+    /// [`Value::Int`] can be written to a `long` field.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Encode`] if the value does not match the schema.
+    pub fn encode(&self, value: &Value) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        self.encode_node(&self.root, value, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Converts JSON into a value of this schema.
+    ///
+    /// Objects become records or maps, and a union takes the first branch
+    /// the JSON converts to. Record fields missing from the JSON are filled
+    /// from the schema default; fields not in the schema are rejected.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Encode`] naming the first field that does not match.
+    pub fn value_from_json(&self, json: &JsonValue) -> Result<Value, Error> {
+        self.json_to_value(&self.root, json)
+    }
+
+    /// Returns the Parsing Canonical Form of the schema.
+    ///
+    /// Two schemas with the same canonical form read and write the same
+    /// payloads, regardless of whitespace, docs, defaults or namespaces.
+    pub fn canonical_form(&self) -> String {
+        let mut out = String::new();
+        write_canonical(&self.root, &mut HashSet::new(), &mut out);
+        out
+    }
+
+    /// Returns the CRC-64-AVRO (Rabin) fingerprint of the canonical form.
+    pub fn fingerprint(&self) -> u64 {
+        rabin_fingerprint(self.canonical_form().as_bytes())
+    }
+
+    /// Returns whether values of `node` take no bytes on the wire.
+    ///
+    /// References are assumed to take bytes, which keeps recursive schemas
+    /// from looping.
+    fn is_zero_width(&self, node: &Node) -> bool {
+        match node {
+            Node::Null => true,
+            Node::Fixed(fixed) => fixed.size == 0,
+            Node::Record(record) => record
+                .fields
+                .iter()
+                .all(|field| self.is_zero_width(&field.schema)),
+            _ => false,
+        }
+    }
+
+    fn decode_node(&self, node: &Node, reader: &mut Reader<'_>) -> Result<Value, Error> {
+        Ok(match self.resolve(node) {
+            Node::Null => Value::Null,
+            Node::Boolean => match reader.read_byte()? {
+                0 => Value::Boolean(false),
+                1 => Value::Boolean(true),
+                b => return Err(decode_error(format!("invalid boolean byte {b}"))),
+            },
+            Node::Int => Value::Int(reader.read_int()?),
+            Node::Long => Value::Long(reader.read_long()?),
+            Node::Float => Value::Float(f32::from_le_bytes(reader.read_array()?)),
+            Node::Double => Value::Double(f64::from_le_bytes(reader.read_array()?)),
+            Node::Bytes => Value::Bytes(reader.read_len_prefixed()?.to_vec()),
+            Node::String => Value::String(reader.read_string()?),
+            Node::Array(items) => {
+                let mut values = Vec::new();
+                reader.read_blocks(self.is_zero_width(items), |reader| {
+                    values.push(self.decode_node(items, reader)?);
+                    Ok(())
+                })?;
+                Value::Array(values)
+            }
+            Node::Map(values) => {
+                let mut entries = BTreeMap::new();
+                // Every entry starts with its key, so entries are never empty.
+                reader.read_blocks(false, |reader| {
+                    let key = reader.read_string()?;
+                    entries.insert(key, self.decode_node(values, reader)?);
+                    Ok(())
+                })?;
+                Value::Map(entries)
+            }
+            Node::Union(branches) => {
+                let index = reader.read_long()?;
+                let branch = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| branches.get(index))
+                    .ok_or_else(|| decode_error(format!("invalid union index {index}")))?;
+                self.decode_node(branch, reader)?
+            }
+            Node::Record(record) => Value::Record(
+                record
+                    .fields
+                    .iter()
+                    .map(|field| Ok((field.name.clone(), self.decode_node(&field.schema, reader)?)))
+                    .collect::<Result<_, Error>>()?,
+            ),
+            Node::Enum(schema) => {
+                let index = reader.read_int()?;
+                let symbol = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| schema.symbols.get(index))
+                    .ok_or_else(|| {
+                        decode_error(format!("invalid index {index} for enum {}", schema.name))
+                    })?;
+                Value::Enum(symbol.clone())
+            }
+            Node::Fixed(fixed) => Value::Fixed(reader.read_bytes(fixed.size)?.to_vec()),
+            Node::Ref(name) => return Err(decode_error(format!("unresolved type {name}"))),
+        })
+    }
+
+    fn encode_node(&self, node: &Node, value: &Value, buf: &mut Vec<u8>) -> Result<(), Error> {
+        let node = self.resolve(node);
+        match (node, value) {
+            (Node::Null, Value::Null) => {}
+            (Node::Boolean, Value::Boolean(b)) => buf.push(u8::from(*b)),
+            (Node::Int, Value::Int(n)) => write_long(buf, i64::from(*n)),
+            (Node::Int, Value::Long(n)) => {
+                let n = i32::try_from(*n)
+                    .map_err(|_| encode_error(format!("{n} is out of range for int")))?;
+                write_long(buf, i64::from(n));
+            }
+            (Node::Long, Value::Int(_) | Value::Long(_)) => {
+                write_long(buf, value.as_long().unwrap_or_default())
+            }
+            (Node::Float, Value::Int(_) | Value::Long(_) | Value::Float(_) | Value::Double(_)) => {
+                buf.extend_from_slice(&(as_double(value) as f32).to_le_bytes())
+            }
+            (Node::Double, Value::Int(_) | Value::Long(_) | Value::Float(_) | Value::Double(_)) => {
+                buf.extend_from_slice(&as_double(value).to_le_bytes())
+            }
+            (Node::Bytes, Value::Bytes(bytes) | Value::Fixed(bytes)) => {
+                write_long(buf, bytes.len() as i64);
+                buf.extend_from_slice(bytes);
+            }
+            (Node::String, Value::String(s)) => {
+                write_long(buf, s.len() as i64);
+                buf.extend_from_slice(s.as_bytes());
+            }
+            (Node::Array(items), Value::Array(values)) => {
+                if !values.is_empty() {
+                    write_long(buf, values.len() as i64);
+                    for (index, value) in values.iter().enumerate() {
+                        self.encode_node(items, value, buf)
+                            .map_err(|e| prefix_path(e, &index.to_string()))?;
+                    }
+                }
+                write_long(buf, 0);
+            }
+            (Node::Map(values), Value::Map(entries)) => {
+                if !entries.is_empty() {
+                    write_long(buf, entries.len() as i64);
+                    for (key, value) in entries {
+                        write_long(buf, key.len() as i64);
+                        buf.extend_from_slice(key.as_bytes());
+                        self.encode_node(values, value, buf)
+                            .map_err(|e| prefix_path(e, key))?;
+                    }
+                }
+                write_long(buf, 0);
+            }
+            (Node::Union(branches), value) => {
+                let index = self.union_branch(branches, value).ok_or_else(|| {
+                    encode_error(format!("no union branch fits {}", value.kind()))
+                })?;
+                write_long(buf, index as i64);
+                self.encode_node(&branches[index], value, buf)?;
+            }
+            (Node::Record(record), Value::Record(fields)) => {
+                for field in &record.fields {
+                    let value = match fields.iter().find(|(name, _)| *name == field.name) {
+                        Some((_, value)) => value,
+                        None => {
+                            let default = field.default.as_ref().ok_or_else(|| {
+                                encode_error(format!(
+                                    "{}: missing field without default",
+                                    field.name
+                                ))
+                            })?;
+                            &self
+                                .default_value(&field.schema, default)
+                                .map_err(|e| prefix_path(e, &field.name))?
+                        }
+                    };
+                    self.encode_node(&field.schema, value, buf)
+                        .map_err(|e| prefix_path(e, &field.name))?;
+                }
+            }
+            (Node::Enum(schema), Value::Enum(symbol) | Value::String(symbol)) => {
+                let index = schema
+                    .symbols
+                    .iter()
+                    .position(|s| s == symbol)
+                    .ok_or_else(|| {
+                        encode_error(format!("{symbol} is not a symbol of enum {}", schema.name))
+                    })?;
+                write_long(buf, index as i64);
+            }
+            (Node::Fixed(fixed), Value::Fixed(bytes) | Value::Bytes(bytes)) => {
+                if bytes.len() != fixed.size {
+                    return Err(encode_error(format!(
+                        "expected {} bytes for {}, found {}",
+                        fixed.size,
+                        fixed.name,
+                        bytes.len()
+                    )));
+                }
+                buf.extend_from_slice(bytes);
+            }
+            (node, value) => {
+                return Err(encode_error(format!(
+                    "expected {}, found {}",
+                    node_kind(node),
+                    value.kind()
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Picks the union branch for `value`, preferring an exact type match
+    /// over a numeric promotion.
+    fn union_branch(&self, branches: &[Node], value: &Value) -> Option<usize> {
+        let exact = |node: &Node| {
+            matches!(
+                (self.resolve(node), value),
+                (Node::Null, Value::Null)
+                    | (Node::Boolean, Value::Boolean(_))
+                    | (Node::Int, Value::Int(_))
+                    | (Node::Long, Value::Long(_))
+                    | (Node::Float, Value::Float(_))
+                    | (Node::Double, Value::Double(_))
+                    | (Node::Bytes, Value::Bytes(_))
+                    | (Node::String, Value::String(_))
+                    | (Node::Array(_), Value::Array(_))
+                    | (Node::Map(_), Value::Map(_))
+                    | (Node::Record(_), Value::Record(_))
+                    | (Node::Enum(_), Value::Enum(_))
+                    | (Node::Fixed(_), Value::Fixed(_))
+            )
+        };
+        let promoted = |node: &Node| match (self.resolve(node), value) {
+            (Node::Int, Value::Long(n)) => i32::try_from(*n).is_ok(),
+            (Node::Long, Value::Int(_)) => true,
+            (Node::Float | Node::Double, Value::Int(_) | Value::Long(_)) => true,
+            (Node::Float, Value::Double(_)) | (Node::Double, Value::Float(_)) => true,
+            (Node::Enum(schema), Value::String(symbol)) => schema.symbols.contains(symbol),
+            (Node::Bytes, Value::Fixed(_)) => true,
+            (Node::Fixed(fixed), Value::Bytes(bytes)) => bytes.len() == fixed.size,
+            _ => false,
+        };
+        branches
+            .iter()
+            .position(exact)
+            .or_else(|| branches.iter().position(promoted))
+    }
+
+    fn json_to_value(&self, node: &Node, json: &JsonValue) -> Result<Value, Error> {
+        let node = self.resolve(node);
+        let mismatch = || {
+            encode_error(format!(
+                "expected {}, found {}",
+                node_kind(node),
+                json_kind(json)
+            ))
+        };
+        Ok(match (node, json) {
+            (Node::Null, JsonValue::Null) => Value::Null,
+            (Node::Boolean, JsonValue::Bool(b)) => Value::Boolean(*b),
+            (Node::Int, JsonValue::Number(n)) => Value::Int(
+                n.as_i64()
+                    .and_then(|n| i32::try_from(n).ok())
+                    .ok_or_else(|| encode_error(format!("{n} is out of range for int")))?,
+            ),
+            (Node::Long, JsonValue::Number(n)) => Value::Long(
+                n.as_i64()
+                    .ok_or_else(|| encode_error(format!("{n} is out of range for long")))?,
+            ),
+            (Node::Float, JsonValue::Number(n)) => {
+                Value::Float(n.as_f64().ok_or_else(mismatch)? as f32)
+            }
+            (Node::Double, JsonValue::Number(n)) => Value::Double(n.as_f64().ok_or_else(mismatch)?),
+            (Node::Bytes | Node::Fixed(_), JsonValue::Array(items)) => {
+                let bytes = items
+                    .iter()
+                    .map(|item| item.as_u64().and_then(|b| u8::try_from(b).ok()))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(mismatch)?;
+                match node {
+                    Node::Fixed(_) => Value::Fixed(bytes),
+                    _ => Value::Bytes(bytes),
+                }
+            }
+            (Node::Bytes, JsonValue::String(s)) => Value::Bytes(s.as_bytes().to_vec()),
+            (Node::String, JsonValue::String(s)) => Value::String(s.clone()),
+            (Node::Enum(schema), JsonValue::String(s)) => {
+                if !schema.symbols.contains(s) {
+                    return Err(encode_error(format!(
+                        "{s} is not a symbol of enum {}",
+                        schema.name
+                    )));
+                }
+                Value::Enum(s.clone())
+            }
+            (Node::Array(items), JsonValue::Array(values)) => Value::Array(
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(index, value)| {
+                        self.json_to_value(items, value)
+                            .map_err(|e| prefix_path(e, &index.to_string()))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            (Node::Map(values), JsonValue::Object(entries)) => Value::Map(
+                entries
+                    .iter()
+                    .map(|(key, value)| {
+                        let value = self
+                            .json_to_value(values, value)
+                            .map_err(|e| prefix_path(e, key))?;
+                        Ok((key.clone(), value))
+                    })
+                    .collect::<Result<_, Error>>()?,
+            ),
+            (Node::Record(record), JsonValue::Object(entries)) => {
+                if let Some(unknown) = entries.keys().find(|key| record.field(key).is_none()) {
+                    return Err(encode_error(format!(
+                        "{unknown}: no such field in {}",
+                        record.name
+                    )));
+                }
+                Value::Record(
+                    record
+                        .fields
+                        .iter()
+                        .map(|field| {
+                            let value = match (entries.get(&field.name), &field.default) {
+                                (Some(json), _) => self.json_to_value(&field.schema, json),
+                                (None, Some(default)) => self.default_value(&field.schema, default),
+                                (None, None) => Err(encode_error("missing field without default")),
+                            };
+                            Ok((
+                                field.name.clone(),
+                                value.map_err(|e| prefix_path(e, &field.name))?,
+                            ))
+                        })
+                        .collect::<Result<_, Error>>()?,
+                )
+            }
+            (Node::Union(branches), json) => {
+                let mut last_error = None;
+                for branch in branches {
+                    match self.json_to_value(branch, json) {
+                        Ok(value) => return Ok(value),
+                        // Prefer the error of a non-null branch, which explains more.
+                        Err(e) if json.is_null() || !matches!(self.resolve(branch), Node::Null) => {
+                            last_error = Some(e)
+                        }
+                        Err(_) => {}
+                    }
+                }
+                return Err(last_error.unwrap_or_else(mismatch));
+            }
+            _ => return Err(mismatch()),
+        })
+    }
+
+    /// Converts a JSON default value into a value of the given schema.
+    ///
+    /// Union defaults apply to the first branch, as the Avro spec requires.
+    fn default_value(&self, node: &Node, json: &JsonValue) -> Result<Value, Error> {
+        let invalid = || encode_error(format!("invalid default {json} for {}", node_kind(node)));
+        Ok(match (self.resolve(node), json) {
+            (Node::Null, JsonValue::Null) => Value::Null,
+            (Node::Boolean, JsonValue::Bool(b)) => Value::Boolean(*b),
+            (Node::Int, JsonValue::Number(n)) => Value::Int(
+                n.as_i64()
+                    .and_then(|n| i32::try_from(n).ok())
+                    .ok_or_else(invalid)?,
+            ),
+            (Node::Long, JsonValue::Number(n)) => Value::Long(n.as_i64().ok_or_else(invalid)?),
+            (Node::Float, JsonValue::Number(n)) => {
+                Value::Float(n.as_f64().ok_or_else(invalid)? as f32)
+            }
+            (Node::Double, JsonValue::Number(n)) => Value::Double(n.as_f64().ok_or_else(invalid)?),
+            // Byte defaults are strings whose code points 0-255 are the bytes.
+            (Node::Bytes | Node::Fixed(_), JsonValue::String(s)) => {
+                let bytes = s
+                    .chars()
+                    .map(|c| u8::try_from(u32::from(c)).map_err(|_| invalid()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match self.resolve(node) {
+                    Node::Fixed(_) => Value::Fixed(bytes),
+                    _ => Value::Bytes(bytes),
+                }
+            }
+            (Node::String, JsonValue::String(s)) => Value::String(s.clone()),
+            (Node::Enum(_), JsonValue::String(s)) => Value::Enum(s.clone()),
+            (Node::Array(items), JsonValue::Array(values)) => Value::Array(
+                values
+                    .iter()
+                    .map(|value| self.default_value(items, value))
+                    .collect::<Result<_, _>>()?,
+            ),
+            (Node::Map(values), JsonValue::Object(entries)) => Value::Map(
+                entries
+                    .iter()
+                    .map(|(key, value)| Ok((key.clone(), self.default_value(values, value)?)))
+                    .collect::<Result<_, Error>>()?,
+            ),
+            (Node::Record(record), JsonValue::Object(entries)) => Value::Record(
+                record
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let json = entries
+                            .get(&field.name)
+                            .or(field.default.as_ref())
+                            .ok_or_else(invalid)?;
+                        Ok((field.name.clone(), self.default_value(&field.schema, json)?))
+                    })
+                    .collect::<Result<_, Error>>()?,
+            ),
+            (Node::Union(branches), json) => {
+                self.default_value(branches.first().ok_or_else(invalid)?, json)?
+            }
+            _ => return Err(invalid()),
+        })
+    }
+}
+
+/// Writes the Parsing Canonical Form of `node`, naming types defined earlier.
+fn write_canonical(node: &Node, defined: &mut HashSet<String>, out: &mut String) {
+    let quoted = |s: &str| serde_json::to_string(s).unwrap_or_default();
+    match node {
+        Node::Null => out.push_str("\"null\""),
+        Node::Boolean => out.push_str("\"boolean\""),
+        Node::Int => out.push_str("\"int\""),
+        Node::Long => out.push_str("\"long\""),
+        Node::Float => out.push_str("\"float\""),
+        Node::Double => out.push_str("\"double\""),
+        Node::Bytes => out.push_str("\"bytes\""),
+        Node::String => out.push_str("\"string\""),
+        Node::Array(items) => {
+            out.push_str("{\"type\":\"array\",\"items\":");
+            write_canonical(items, defined, out);
+            out.push('}');
+        }
+        Node::Map(values) => {
+            out.push_str("{\"type\":\"map\",\"values\":");
+            write_canonical(values, defined, out);
+            out.push('}');
+        }
+        Node::Union(branches) => {
+            out.push('[');
+            for (index, branch) in branches.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_canonical(branch, defined, out);
+            }
+            out.push(']');
+        }
+        Node::Record(record) if defined.insert(record.name.clone()) => {
+            out.push_str(&format!(
+                "{{\"name\":{},\"type\":\"record\",\"fields\":[",
+                quoted(&record.name)
+            ));
+            for (index, field) in record.fields.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push_str(&format!("{{\"name\":{},\"type\":", quoted(&field.name)));
+                write_canonical(&field.schema, defined, out);
+                out.push('}');
+            }
+            out.push_str("]}");
+        }
+        Node::Enum(schema) if defined.insert(schema.name.clone()) => {
+            let symbols: Vec<_> = schema.symbols.iter().map(|s| quoted(s)).collect();
+            out.push_str(&format!(
+                "{{\"name\":{},\"type\":\"enum\",\"symbols\":[{}]}}",
+                quoted(&schema.name),
+                symbols.join(",")
+            ));
+        }
+        Node::Fixed(fixed) if defined.insert(fixed.name.clone()) => {
+            out.push_str(&format!(
+                "{{\"name\":{},\"type\":\"fixed\",\"size\":{}}}",
+                quoted(&fixed.name),
+                fixed.size
+            ));
+        }
+        Node::Record(record) => out.push_str(&quoted(&record.name)),
+        Node::Enum(schema) => out.push_str(&quoted(&schema.name)),
+        Node::Fixed(fixed) => out.push_str(&quoted(&fixed.name)),
+        Node::Ref(name) => out.push_str(&quoted(name)),
+    }
+}
+
+/// Computes the CRC-64-AVRO fingerprint defined by the Avro specification.
+fn rabin_fingerprint(bytes: &[u8]) -> u64 {
+    const EMPTY: u64 = 0xc15d_213a_a4d7_a795;
+    let table: [u64; 256] = std::array::from_fn(|i| {
+        (0..8).fold(i as u64, |fp, _| {
+            (fp >> 1) ^ (EMPTY & (fp & 1).wrapping_neg())
+        })
+    });
+    bytes.iter().fold(EMPTY, |fp, byte| {
+        (fp >> 8) ^ table[((fp ^ u64::from(*byte)) & 0xff) as usize]
+    })
+}
+
+/// Builds the named type table while parsing a schema.
+#[derive(Default)]
+struct Parser {
+    names: HashMap<String, Node>,
+    /// Named types whose definition is still being parsed.
+    defining: HashSet<String>,
+}
+
+impl Parser {
+    fn parse(&mut self, json: &JsonValue, namespace: Option<&str>) -> Result<Node, Error> {
+        match json {
+            JsonValue::String(name) => self.parse_name(name, namespace),
+            JsonValue::Array(branches) => {
+                let branches = branches
+                    .iter()
+                    .map(|branch| self.parse(branch, namespace))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if branches
+                    .iter()
+                    .any(|branch| matches!(branch, Node::Union(_)))
+                {
+                    return Err(invalid_schema("unions may not immediately contain unions"));
+                }
+                Ok(Node::Union(branches))
+            }
+            JsonValue::Object(object) => match object.get("type") {
+                Some(JsonValue::String(kind)) => match kind.as_str() {
+                    "record" | "error" => self.parse_record(object, namespace),
+                    "enum" => self.parse_enum(object, namespace),
+                    "fixed" => self.parse_fixed(object, namespace),
+                    "array" => Ok(Node::Array(Box::new(
+                        self.parse(required(object, "items")?, namespace)?,
+                    ))),
+                    "map" => Ok(Node::Map(Box::new(
+                        self.parse(required(object, "values")?, namespace)?,
+                    ))),
+                    name => self.parse_name(name, namespace),
+                },
+                Some(json) => self.parse(json, namespace),
+                None => Err(invalid_schema("object without \"type\"")),
+            },
+            json => Err(invalid_schema(format!("unexpected {json}"))),
+        }
+    }
+
+    /// Resolves a primitive type name or a reference to a named type.
+    fn parse_name(&mut self, name: &str, namespace: Option<&str>) -> Result<Node, Error> {
+        let primitive = match name {
+            "null" => Node::Null,
+            "boolean" => Node::Boolean,
+            "int" => Node::Int,
+            "long" => Node::Long,
+            "float" => Node::Float,
+            "double" => Node::Double,
+            "bytes" => Node::Bytes,
+            "string" => Node::String,
+            _ => {
+                // Unqualified names are looked up in the enclosing namespace
+                // first, then in the null namespace.
+                let candidates = [full_name(name, namespace), name.to_string()];
+                for candidate in candidates {
+                    if let Some(node) = self.names.get(&candidate) {
+                        return Ok(node.clone());
+                    }
+                    if self.defining.contains(&candidate) {
+                        return Ok(Node::Ref(candidate));
+                    }
+                }
+                return Err(invalid_schema(format!("unknown type {name}")));
+            }
+        };
+        Ok(primitive)
+    }
+
+    fn parse_record(
+        &mut self,
+        object: &serde_json::Map<String, JsonValue>,
+        namespace: Option<&str>,
+    ) -> Result<Node, Error> {
+        let name = self.define(object, namespace)?;
+        let namespace = name.rsplit_once('.').map(|(namespace, _)| namespace);
+        let fields = required(object, "fields")?
+            .as_array()
+            .ok_or_else(|| invalid_schema(format!("fields of {name} is not an array")))?
+            .iter()
+            .map(|field| {
+                let field_name = required_str(field, "name")?;
+                Ok(Field {
+                    name: field_name.to_string(),
+                    doc: field
+                        .get("doc")
+                        .and_then(JsonValue::as_str)
+                        .map(str::to_string),
+                    schema: self.parse(
+                        field.get("type").ok_or_else(|| {
+                            invalid_schema(format!("field {field_name} has no type"))
+                        })?,
+                        namespace,
+                    )?,
+                    default: field.get("default").cloned(),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let node = Node::Record(Arc::new(Record {
+            doc: object
+                .get("doc")
+                .and_then(JsonValue::as_str)
+                .map(str::to_string),
+            fields,
+            name: name.clone(),
+        }));
+        Ok(self.register(name, node))
+    }
+
+    fn parse_enum(
+        &mut self,
+        object: &serde_json::Map<String, JsonValue>,
+        namespace: Option<&str>,
+    ) -> Result<Node, Error> {
+        let name = self.define(object, namespace)?;
+        let symbols = required(object, "symbols")?
+            .as_array()
+            .and_then(|symbols| {
+                symbols
+                    .iter()
+                    .map(|symbol| symbol.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .ok_or_else(|| invalid_schema(format!("symbols of {name} is not a string array")))?;
+        let node = Node::Enum(Arc::new(Enum {
+            name: name.clone(),
+            symbols,
+        }));
+        Ok(self.register(name, node))
+    }
+
+    fn parse_fixed(
+        &mut self,
+        object: &serde_json::Map<String, JsonValue>,
+        namespace: Option<&str>,
+    ) -> Result<Node, Error> {
+        let name = self.define(object, namespace)?;
+        let size = required(object, "size")?
+            .as_u64()
+            .and_then(|size| usize::try_from(size).ok())
+            .ok_or_else(|| invalid_schema(format!("size of {name} is not a number")))?;
+        let node = Node::Fixed(Arc::new(Fixed {
+            name: name.clone(),
+            size,
+        }));
+        Ok(self.register(name, node))
+    }
+
+    /// Starts the definition of a named type and returns its full name.
+    fn define(
+        &mut self,
+        object: &serde_json::Map<String, JsonValue>,
+        namespace: Option<&str>,
+    ) -> Result<String, Error> {
+        let name = object
+            .get("name")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| invalid_schema("named type without \"name\""))?;
+        let namespace = object
+            .get("namespace")
+            .and_then(JsonValue::as_str)
+            .or(namespace);
+        let name = full_name(name, namespace);
+        if self.names.contains_key(&name) || !self.defining.insert(name.clone()) {
+            return Err(invalid_schema(format!("duplicate definition of {name}")));
+        }
+        Ok(name)
+    }
+
+    /// Finishes the definition of a named type.
+    fn register(&mut self, name: String, node: Node) -> Node {
+        self.defining.remove(&name);
+        self.names.insert(name, node.clone());
+        node
+    }
+}
+
+/// Reads Avro binary encoded data from a byte slice.
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.buf.len() < len {
+            return Err(decode_error(format!(
+                "needed {len} bytes, {} remaining",
+                self.buf.len()
+            )));
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    /// Reads a zig-zag encoded variable-length integer.
+    fn read_long(&mut self) -> Result<i64, Error> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+            }
+        }
+        Err(decode_error("variable-length integer is too long"))
+    }
+
+    fn read_int(&mut self) -> Result<i32, Error> {
+        let value = self.read_long()?;
+        i32::try_from(value).map_err(|_| decode_error(format!("{value} is out of range for int")))
+    }
+
+    fn read_len_prefixed(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.read_long()?;
+        let len =
+            usize::try_from(len).map_err(|_| decode_error(format!("negative length {len}")))?;
+        self.read_bytes(len)
+    }
+
+    fn read_string(&mut self) -> Result<String, Error> {
+        let bytes = self.read_len_prefixed()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| decode_error("string is not valid UTF-8"))
+    }
+
+    /// Reads the blocks of an array or map, calling `read_item` per item.
+    ///
+    /// Items that take at least one byte cannot outnumber the remaining
+    /// bytes, which bounds the work a corrupt count can cause. Zero-width
+    /// items are bounded by [`MAX_ZERO_WIDTH_ITEMS`] instead.
+    fn read_blocks(
+        &mut self,
+        zero_width: bool,
+        mut read_item: impl FnMut(&mut Self) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        loop {
+            let count = self.read_long()?;
+            if count == 0 {
+                return Ok(());
+            }
+            if count < 0 {
+                // Negative counts are followed by the block size in bytes.
+                self.read_long()?;
+            }
+            let limit = if zero_width {
+                MAX_ZERO_WIDTH_ITEMS
+            } else {
+                self.buf.len() as u64
+            };
+            if count.unsigned_abs() > limit {
+                return Err(decode_error(format!(
+                    "block of {count} items exceeds the remaining payload"
+                )));
+            }
+            for _ in 0..count.unsigned_abs() {
+                read_item(self)?;
+            }
+        }
+    }
+}
+
+/// Writes a zig-zag encoded variable-length integer.
+fn write_long(buf: &mut Vec<u8>, value: i64) {
+    let mut value = ((value << 1) ^ (value >> 63)) as u64;
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn as_double(value: &Value) -> f64 {
+    match value {
+        Value::Int(n) => f64::from(*n),
+        Value::Long(n) => *n as f64,
+        Value::Float(n) => f64::from(*n),
+        Value::Double(n) => *n,
+        _ => f64::NAN,
+    }
+}
+
+fn json_kind(json: &JsonValue) -> &'static str {
+    match json {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "boolean",
+        JsonValue::Number(_) => "number",
+        JsonValue::String(_) => "string",
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "object",
+    }
+}
+
+fn node_kind(node: &Node) -> String {
+    match node {
+        Node::Null => "null".to_string(),
+        Node::Boolean => "boolean".to_string(),
+        Node::Int => "int".to_string(),
+        Node::Long => "long".to_string(),
+        Node::Float => "float".to_string(),
+        Node::Double => "double".to_string(),
+        Node::Bytes => "bytes".to_string(),
+        Node::String => "string".to_string(),
+        Node::Array(_) => "array".to_string(),
+        Node::Map(_) => "map".to_string(),
+        Node::Union(_) => "union".to_string(),
+        Node::Record(record) => format!("record {}", record.name),
+        Node::Enum(schema) => format!("enum {}", schema.name),
+        Node::Fixed(fixed) => format!("fixed {}", fixed.name),
+        Node::Ref(name) => name.clone(),
+    }
+}
+
+fn full_name(name: &str, namespace: Option<&str>) -> String {
+    match namespace {
+        Some(namespace) if !namespace.is_empty() && !name.contains('.') => {
+            format!("{namespace}.{name}")
+        }
+        _ => name.to_string(),
+    }
+}
+
+fn required<'a>(
+    object: &'a serde_json::Map<String, JsonValue>,
+    key: &str,
+) -> Result<&'a JsonValue, Error> {
+    object
+        .get(key)
+        .ok_or_else(|| invalid_schema(format!("missing \"{key}\"")))
+}
+
+fn required_str<'a>(json: &'a JsonValue, key: &str) -> Result<&'a str, Error> {
+    json.get(key)
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| invalid_schema(format!("missing \"{key}\"")))
+}
+
+fn invalid_schema(message: impl Into<String>) -> Error {
+    Error::InvalidSchema {
+        message: message.into(),
+    }
+}
+
+fn decode_error(message: impl Into<String>) -> Error {
+    Error::Decode {
+        message: message.into(),
+    }
+}
+
+fn encode_error(message: impl Into<String>) -> Error {
+    Error::Encode {
+        message: message.into(),
+    }
+}
+
+/// Prefixes the message of an encode error with a field name or index.
+fn prefix_path(error: Error, segment: &str) -> Error {
+    match error {
+        Error::Encode { message } if message.contains(": ") => Error::Encode {
+            message: format!("{segment}.{message}"),
+        },
+        Error::Encode { message } => Error::Encode {
+            message: format!("{segment}: {message}"),
+        },
+        error => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLATFORM_EVENT_SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "Order_Placed__e",
+        "namespace": "com.sforce.eventbus",
+        "fields": [
+            {"name": "CreatedDate", "type": "long", "doc": "CreatedDate:DateTime"},
+            {"name": "CreatedById", "type": "string", "doc": "CreatedBy:EntityId"},
+            {"name": "Order_Number__c", "type": ["null", "string"], "doc": "Data:Text:00N5e00000ABCDE", "default": null},
+            {"name": "Amount__c", "type": ["null", "double"], "doc": "Data:Double:00N5e00000ABCDF", "default": null}
+        ]
+    }"#;
+
+    const CHANGE_EVENT_SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "AccountChangeEvent",
+        "namespace": "com.sforce.eventbus",
+        "fields": [
+            {"name": "ChangeEventHeader", "type": {
+                "type": "record",
+                "name": "ChangeEventHeader",
+                "fields": [
+                    {"name": "entityName", "type": "string"},
+                    {"name": "recordIds", "type": {"type": "array", "items": "string"}},
+                    {"name": "changeType", "type": {
+                        "type": "enum",
+                        "name": "ChangeType",
+                        "symbols": ["CREATE", "UPDATE", "DELETE", "UNDELETE", "GAP_CREATE", "GAP_UPDATE", "GAP_DELETE", "GAP_UNDELETE", "GAP_OVERFLOW", "SNAPSHOT"]
+                    }},
+                    {"name": "changeOrigin", "type": "string"},
+                    {"name": "transactionKey", "type": "string"},
+                    {"name": "sequenceNumber", "type": "int"},
+                    {"name": "commitTimestamp", "type": "long"},
+                    {"name": "commitNumber", "type": "long"},
+                    {"name": "commitUser", "type": "string"},
+                    {"name": "nulledFields", "type": {"type": "array", "items": "string"}},
+                    {"name": "diffFields", "type": {"type": "array", "items": "string"}},
+                    {"name": "changedFields", "type": {"type": "array", "items": "string"}}
+                ]
+            }},
+            {"name": "Name", "type": ["null", "string"], "default": null},
+            {"name": "Type", "type": ["null", "string"], "default": null},
+            {"name": "BillingAddress", "type": ["null", {
+                "type": "record",
+                "name": "Address",
+                "fields": [
+                    {"name": "Street", "type": ["null", "string"], "default": null},
+                    {"name": "City", "type": ["null", "string"], "default": null},
+                    {"name": "State", "type": ["null", "string"], "default": null},
+                    {"name": "PostalCode", "type": ["null", "string"], "default": null},
+                    {"name": "Country", "type": ["null", "string"], "default": null}
+                ]
+            }], "default": null},
+            {"name": "LastModifiedDate", "type": ["null", "long"], "default": null}
+        ]
+    }"#;
+
+    fn platform_event_payload() -> Vec<u8> {
+        [
+            &[0x80, 0xa0, 0xab, 0xfe, 0xf9, 0x62][..], // CreatedDate
+            b"\x24005xx000001SwiUAAS",                 // CreatedById
+            b"\x02\x10ORD-1001",                       // Order_Number__c
+            &[0x02, 0x48, 0xe1, 0x7a, 0x14, 0xae, 0xbf, 0x62, 0x40], // Amount__c
+        ]
+        .concat()
+    }
+
+    fn change_event_payload() -> Vec<u8> {
+        [
+            &b"\x0eAccount"[..],                         // entityName
+            b"\x02\x24001xx000003DGb2AAG\x00",           // recordIds
+            b"\x02",                                     // changeType UPDATE
+            b"\x00",                                     // changeOrigin
+            b"\x08tx-1",                                 // transactionKey
+            b"\x02",                                     // sequenceNumber
+            &[0x80, 0xa0, 0xab, 0xfe, 0xf9, 0x62],       // commitTimestamp
+            &[0xda, 0x8a, 0xf6, 0xb6, 0x57],             // commitNumber
+            b"\x24005xx000001SwiUAAS",                   // commitUser
+            b"\x00",                                     // nulledFields
+            b"\x00",                                     // diffFields
+            b"\x02\x080x06\x00",                         // changedFields
+            b"\x02\x08Acme",                             // Name
+            b"\x00",                                     // Type
+            b"\x02\x00\x02\x0aParis\x00\x00\x02\x04FR",  // BillingAddress
+            &[0x02, 0x80, 0xa0, 0xab, 0xfe, 0xf9, 0x62], // LastModifiedDate
+        ]
+        .concat()
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn test_decode_platform_event() {
+        let schema = Schema::parse(PLATFORM_EVENT_SCHEMA).unwrap();
+        let event = schema.decode(&platform_event_payload()).unwrap();
+        assert_eq!(
+            event,
+            Value::Record(vec![
+                ("CreatedDate".to_string(), Value::Long(1_700_000_000_000)),
+                ("CreatedById".to_string(), string("005xx000001SwiUAAS")),
+                ("Order_Number__c".to_string(), string("ORD-1001")),
+                ("Amount__c".to_string(), Value::Double(149.99)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_encode_platform_event() {
+        let schema = Schema::parse(PLATFORM_EVENT_SCHEMA).unwrap();
+        let event = Value::Record(vec![
+            ("CreatedDate".to_string(), Value::Long(1_700_000_000_000)),
+            ("CreatedById".to_string(), string("005xx000001SwiUAAS")),
+            ("Order_Number__c".to_string(), string("ORD-1001")),
+            ("Amount__c".to_string(), Value::Double(149.99)),
+        ]);
+        assert_eq!(schema.encode(&event).unwrap(), platform_event_payload());
+    }
+
+    #[test]
+    fn test_encode_uses_defaults_and_promotions() {
+        let schema = Schema::parse(PLATFORM_EVENT_SCHEMA).unwrap();
+        let event = Value::Record(vec![
+            ("CreatedDate".to_string(), Value::Int(7)),
+            ("CreatedById".to_string(), string("005xx000001SwiUAAS")),
+            ("Amount__c".to_string(), Value::Long(10)),
+        ]);
+        let decoded = schema.decode(&schema.encode(&event).unwrap()).unwrap();
+        assert_eq!(decoded.field("CreatedDate"), Some(&Value::Long(7)));
+        assert_eq!(decoded.field("Order_Number__c"), Some(&Value::Null));
+        assert_eq!(decoded.field("Amount__c"), Some(&Value::Double(10.0)));
+    }
+
+    #[test]
+    fn test_encode_mismatch_names_field() {
+        let schema = Schema::parse(PLATFORM_EVENT_SCHEMA).unwrap();
+        let missing = Value::Record(vec![("CreatedDate".to_string(), Value::Long(1))]);
+        let error = schema.encode(&missing).unwrap_err().to_string();
+        assert!(error.contains("CreatedById: missing field"), "{error}");
+
+        let wrong_type = Value::Record(vec![
+            ("CreatedDate".to_string(), string("yesterday")),
+            ("CreatedById".to_string(), string("005xx000001SwiUAAS")),
+        ]);
+        let error = schema.encode(&wrong_type).unwrap_err().to_string();
+        assert!(
+            error.contains("CreatedDate: expected long, found string"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_decode_change_event() {
+        let schema = Schema::parse(CHANGE_EVENT_SCHEMA).unwrap();
+        let event = schema.decode(&change_event_payload()).unwrap();
+
+        let header = event.field("ChangeEventHeader").unwrap();
+        assert_eq!(
+            header.field("entityName").and_then(Value::as_str),
+            Some("Account")
+        );
+        assert_eq!(
+            header.field("changeType"),
+            Some(&Value::Enum("UPDATE".to_string()))
+        );
+        assert_eq!(header.field("sequenceNumber"), Some(&Value::Int(1)));
+        assert_eq!(
+            header.field("commitNumber").and_then(Value::as_long),
+            Some(11_734_532_781)
+        );
+        assert_eq!(
+            header.field("recordIds").and_then(Value::as_array),
+            Some(&[string("001xx000003DGb2AAG")][..])
+        );
+        assert_eq!(
+            header.field("changedFields").and_then(Value::as_array),
+            Some(&[string("0x06")][..])
+        );
+        assert_eq!(event.field("Type"), Some(&Value::Null));
+
+        let address = event.field("BillingAddress").unwrap();
+        assert_eq!(address.field("City").and_then(Value::as_str), Some("Paris"));
+        assert_eq!(address.field("Street"), Some(&Value::Null));
+
+        assert_eq!(schema.encode(&event).unwrap(), change_event_payload());
+    }
+
+    #[test]
+    fn test_parse_named_types() {
+        let schema = Schema::parse(CHANGE_EVENT_SCHEMA).unwrap();
+        assert!(matches!(
+            schema.named("com.sforce.eventbus.ChangeType"),
+            Some(Node::Enum(e)) if e.symbols.len() == 10
+        ));
+        let Node::Record(record) = schema.root() else {
+            panic!("root is not a record");
+        };
+        assert_eq!(record.name, "com.sforce.eventbus.AccountChangeEvent");
+        assert!(record.field("BillingAddress").is_some());
+    }
+
+    #[test]
+    fn test_recursive_schema() {
+        let schema = Schema::parse(
+            r#"{"type": "record", "name": "Node", "fields": [
+                {"name": "value", "type": "int"},
+                {"name": "next", "type": ["null", "Node"]}
+            ]}"#,
+        )
+        .unwrap();
+        let list = Value::Record(vec![
+            ("value".to_string(), Value::Int(1)),
+            (
+                "next".to_string(),
+                Value::Record(vec![
+                    ("value".to_string(), Value::Int(2)),
+                    ("next".to_string(), Value::Null),
+                ]),
+            ),
+        ]);
+        let payload = schema.encode(&list).unwrap();
+        assert_eq!(payload, vec![0x02, 0x02, 0x04, 0x00]);
+        assert_eq!(schema.decode(&payload).unwrap(), list);
+    }
+
+    #[test]
+    fn test_map_fixed_and_negative_block_count() {
+        let schema = Schema::parse(
+            r#"{"type": "record", "name": "R", "fields": [
+                {"name": "tags", "type": {"type": "map", "values": "boolean"}},
+                {"name": "id", "type": {"type": "fixed", "name": "Id", "size": 2}}
+            ]}"#,
+        )
+        .unwrap();
+        // Block with a negative count of -1 followed by its size in bytes.
+        let payload = [0x01, 0x06, 0x02, b'a', 0x01, 0x00, 0xab, 0xcd];
+        let value = schema.decode(&payload).unwrap();
+        assert_eq!(
+            value.field("tags"),
+            Some(&Value::Map(BTreeMap::from([(
+                "a".to_string(),
+                Value::Boolean(true)
+            )])))
+        );
+        assert_eq!(value.field("id"), Some(&Value::Fixed(vec![0xab, 0xcd])));
+    }
+
+    #[test]
+    fn test_decode_malformed_payload() {
+        let schema = Schema::parse(PLATFORM_EVENT_SCHEMA).unwrap();
+        let payload = platform_event_payload();
+        assert!(matches!(
+            schema.decode(&payload[..payload.len() - 1]),
+            Err(Error::Decode { .. })
+        ));
+
+        let mut trailing = payload.clone();
+        trailing.push(0);
+        assert!(matches!(
+            schema.decode(&trailing),
+            Err(Error::Decode { .. })
+        ));
+
+        let mut bad_union = payload;
+        bad_union[25] = 0x04;
+        assert!(matches!(
+            schema.decode(&bad_union),
+            Err(Error::Decode { .. })
+        ));
+
+        let array = Schema::parse(r#"{"type": "array", "items": "null"}"#).unwrap();
+        assert!(matches!(
+            array.decode(&[0xfe, 0xff, 0xff, 0xff, 0x0f]),
+            Err(Error::Decode { .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_array_of_zero_width_items() {
+        let schema = Schema::parse(
+            r#"{"type": "array", "items": {"type": "record", "name": "Empty", "fields": [
+                {"name": "nothing", "type": "null"}
+            ]}}"#,
+        )
+        .unwrap();
+        let value = Value::Array(vec![
+            Value::Record(vec![(
+                "nothing".to_string(),
+                Value::Null
+            )]);
+            3
+        ]);
+        let payload = schema.encode(&value).unwrap();
+        assert_eq!(payload, vec![0x06, 0x00]);
+        assert_eq!(schema.decode(&payload).unwrap(), value);
+
+        let nulls = Schema::parse(r#"{"type": "array", "items": "null"}"#).unwrap();
+        let value = nulls.decode(&[0x06, 0x00]).unwrap();
+        assert_eq!(value, Value::Array(vec![Value::Null; 3]));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let schema = Schema::parse(CHANGE_EVENT_SCHEMA).unwrap();
+        let event = schema.decode(&change_event_payload()).unwrap();
+        let json = JsonValue::from(event.clone());
+        assert_eq!(json["ChangeEventHeader"]["changeType"], "UPDATE");
+        assert_eq!(json["BillingAddress"]["City"], "Paris");
+        assert_eq!(json["Type"], JsonValue::Null);
+        assert_eq!(schema.value_from_json(&json).unwrap(), event);
+    }
+
+    #[test]
+    fn test_value_from_json_mismatch() {
+        let schema = Schema::parse(CHANGE_EVENT_SCHEMA).unwrap();
+        let mut json = JsonValue::from(schema.decode(&change_event_payload()).unwrap());
+
+        json["BillingAddress"]["City"] = JsonValue::from(7);
+        let error = schema.value_from_json(&json).unwrap_err().to_string();
+        assert!(
+            error.contains("BillingAddress.City: expected string, found number"),
+            "{error}"
+        );
+
+        json["BillingAddress"] = JsonValue::Null;
+        json["Industry"] = JsonValue::from("Energy");
+        let error = schema.value_from_json(&json).unwrap_err().to_string();
+        assert!(error.contains("Industry: no such field"), "{error}");
+
+        let object = json.as_object_mut().unwrap();
+        object.remove("Industry");
+        object.remove("Name");
+        let value = schema.value_from_json(&json).unwrap();
+        assert_eq!(value.field("Name"), Some(&Value::Null));
+    }
+
+    #[test]
+    fn test_canonical_form_and_fingerprint() {
+        let schema = Schema::parse(r#"{"type": "int", "logicalType": "date"}"#).unwrap();
+        assert_eq!(schema.canonical_form(), r#""int""#);
+        let null = Schema::parse(r#""null""#).unwrap();
+        assert_eq!(null.fingerprint(), 7_195_948_357_588_979_594);
+
+        let schema = Schema::parse(CHANGE_EVENT_SCHEMA).unwrap();
+        let canonical = schema.canonical_form();
+        assert!(canonical.starts_with(
+            r#"{"name":"com.sforce.eventbus.AccountChangeEvent","type":"record","fields":[{"name":"ChangeEventHeader","type":{"name":"com.sforce.eventbus.ChangeEventHeader""#
+        ));
+        assert!(!canonical.contains("default"));
+
+        // Whitespace, docs and defaults do not change the fingerprint.
+        let reparsed = Schema::parse(&canonical).unwrap();
+        assert_eq!(reparsed.canonical_form(), canonical);
+        assert_eq!(reparsed.fingerprint(), schema.fingerprint());
+        assert_ne!(
+            Schema::parse(PLATFORM_EVENT_SCHEMA).unwrap().fingerprint(),
+            schema.fingerprint()
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_schema() {
+        assert!(matches!(Schema::parse("{"), Err(Error::ParseSchema { .. })));
+        assert!(matches!(
+            Schema::parse(r#""Missing""#),
+            Err(Error::InvalidSchema { .. })
+        ));
+        assert!(matches!(
+            Schema::parse(r#"{"type": "record", "name": "R"}"#),
+            Err(Error::InvalidSchema { .. })
+        ));
+        assert!(matches!(
+            Schema::parse(r#"["null", ["int"]]"#),
+            Err(Error::InvalidSchema { .. })
+        ));
+    }
+}
@@ -0,0 +1,499 @@
+use crate::pubsub::avro::{self, Node, Record, Value};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Name of the header field present in every change event schema.
+const HEADER_FIELD: &str = "ChangeEventHeader";
+
+/// Errors that can occur while interpreting a change event.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The event is not a record with a `ChangeEventHeader` field.
+    #[error("Event has no ChangeEventHeader")]
+    MissingHeader,
+    /// The `ChangeEventHeader` does not have the expected fields.
+    #[error("Invalid ChangeEventHeader: {source}")]
+    InvalidHeader {
+        #[source]
+        source: serde_json::Error,
+    },
+    /// A field bitmap does not match the schema.
+    #[error("Invalid field bitmap {bitmap}: {message}")]
+    InvalidBitmap {
+        /// The bitmap as sent in the header.
+        bitmap: String,
+        /// Description of the problem.
+        message: String,
+    },
+    /// The payload could not be decoded.
+    #[error("Failed to decode change event: {source}")]
+    Decode {
+        #[source]
+        source: avro::Error,
+    },
+}
+
+/// Type of change captured by a change event.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[non_exhaustive]
+pub enum ChangeType {
+    /// Records were created.
+    Create,
+    /// Records were updated.
+    Update,
+    /// Records were deleted.
+    Delete,
+    /// Records were restored from the Recycle Bin.
+    Undelete,
+    /// Records were created, but the change could not be captured.
+    GapCreate,
+    /// Records were updated, but the change could not be captured.
+    GapUpdate,
+    /// Records were deleted, but the change could not be captured.
+    GapDelete,
+    /// Records were undeleted, but the change could not be captured.
+    GapUndelete,
+    /// A transaction changed more records than change events are sent for.
+    GapOverflow,
+    /// A change type this version does not know about.
+    #[serde(other)]
+    Unknown,
+}
+
+impl ChangeType {
+    /// Returns whether this is a gap event, including [`ChangeType::GapOverflow`].
+    pub fn is_gap(&self) -> bool {
+        matches!(
+            self,
+            ChangeType::GapCreate
+                | ChangeType::GapUpdate
+                | ChangeType::GapDelete
+                | ChangeType::GapUndelete
+                | ChangeType::GapOverflow
+        )
+    }
+}
+
+/// Header fields common to all change events.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeEventHeader {
+    /// API name of the changed object, e.g. `Account`.
+    pub entity_name: String,
+    /// IDs of the changed records. One event can cover several records
+    /// changed the same way in one transaction.
+    pub record_ids: Vec<String>,
+    /// Type of change.
+    pub change_type: ChangeType,
+    /// Client that made the change, if known.
+    #[serde(default)]
+    pub change_origin: String,
+    /// ID of the transaction that made the change.
+    pub transaction_key: String,
+    /// Position of this event among the events of its transaction.
+    pub sequence_number: i32,
+    /// Commit time in milliseconds since the Unix epoch.
+    pub commit_timestamp: i64,
+    /// System change number of the transaction.
+    pub commit_number: i64,
+    /// ID of the user who made the change.
+    pub commit_user: String,
+}
+
+impl ChangeEventHeader {
+    /// Returns the commit time.
+    pub fn commit_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.commit_timestamp.max(0) as u64)
+    }
+}
+
+/// Record change with its field values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordChange {
+    /// Event header.
+    pub header: ChangeEventHeader,
+    /// Fields set by the change, e.g. `Name` or `BillingAddress.City`.
+    pub changed_fields: Vec<String>,
+    /// Fields set to null by the change.
+    pub nulled_fields: Vec<String>,
+    /// Text fields whose value is a diff against the previous value.
+    pub diff_fields: Vec<String>,
+    /// The decoded event, including the header.
+    pub value: Value,
+}
+
+impl RecordChange {
+    /// Returns the value of a field, using `Parent.Child` for the fields of
+    /// compound fields such as `BillingAddress.City`.
+    pub fn field(&self, path: &str) -> Option<&Value> {
+        path.split('.')
+            .try_fold(&self.value, |value, name| value.field(name))
+    }
+
+    /// Returns the changed fields with their new values.
+    pub fn changed_values(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.changed_fields
+            .iter()
+            .filter_map(|name| Some((name.as_str(), self.field(name)?)))
+    }
+}
+
+/// Change Data Capture event.
+///
+/// Gap and overflow events carry no field values: they tell the subscriber
+/// that changes were made without being captured, so the affected records
+/// must be read again from the org.
+///
+/// # Examples
+///
+/// ```no_run
+/// use salesforce_core::pubsub::cdc::ChangeEvent;
+/// use salesforce_core::pubsub::schema_cache::SchemaCache;
+/// use salesforce_pubsub_v1::eventbus::v1::ProducerEvent;
+///
+/// # async fn example(cache: SchemaCache, event: ProducerEvent) -> Result<(), Box<dyn std::error::Error>> {
+/// let schema = cache.get(&event.schema_id).await?;
+/// match ChangeEvent::decode(&schema.schema, &event.payload)? {
+///     ChangeEvent::Record(change) => {
+///         for (field, value) in change.changed_values() {
+///             println!("{:?} {field} = {value:?}", change.header.record_ids);
+///         }
+///     }
+///     ChangeEvent::Gap(header) => println!("Re-read {:?}", header.record_ids),
+///     ChangeEvent::Overflow(header) => println!("Re-read all {}", header.entity_name),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeEvent {
+    /// Create, update, delete or undelete with the changed field values.
+    Record(RecordChange),
+    /// Records listed in the header changed without the change being
+    /// captured.
+    Gap(ChangeEventHeader),
+    /// More records changed in one transaction than events are sent for.
+    /// The header names the object, but not every affected record.
+    Overflow(ChangeEventHeader),
+}
+
+impl ChangeEvent {
+    /// Decodes a change event payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the payload does not match the schema or is not a
+    /// change event.
+    pub fn decode(schema: &avro::Schema, payload: &[u8]) -> Result<Self, Error> {
+        let value = schema
+            .decode(payload)
+            .map_err(|e| Error::Decode { source: e })?;
+        Self::from_value(schema, value)
+    }
+
+    /// Interprets a decoded change event.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is not a change event, or its field
+    /// bitmaps do not match the schema.
+    pub fn from_value(schema: &avro::Schema, value: Value) -> Result<Self, Error> {
+        let header_value = value.field(HEADER_FIELD).ok_or(Error::MissingHeader)?;
+        let header_json = JsonValue::from(header_value.clone());
+        let header: ChangeEventHeader = serde_json::from_value(header_json.clone())
+            .map_err(|e| Error::InvalidHeader { source: e })?;
+
+        match header.change_type {
+            ChangeType::GapOverflow => return Ok(ChangeEvent::Overflow(header)),
+            ref change_type if change_type.is_gap() => return Ok(ChangeEvent::Gap(header)),
+            _ => {}
+        }
+
+        let Node::Record(record) = schema.resolve(schema.root()) else {
+            return Err(Error::MissingHeader);
+        };
+        let bitmap_fields = |name: &str| -> Result<Vec<String>, Error> {
+            let bitmaps: Vec<String> = header_json
+                .get(name)
+                .cloned()
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(|e| Error::InvalidHeader { source: e })?
+                .unwrap_or_default();
+            resolve_field_names(schema, record, &bitmaps)
+        };
+
+        Ok(ChangeEvent::Record(RecordChange {
+            changed_fields: bitmap_fields("changedFields")?,
+            nulled_fields: bitmap_fields("nulledFields")?,
+            diff_fields: bitmap_fields("diffFields")?,
+            header,
+            value,
+        }))
+    }
+
+    /// Returns the event header.
+    pub fn header(&self) -> &ChangeEventHeader {
+        match self {
+            ChangeEvent::Record(change) => &change.header,
+            ChangeEvent::Gap(header) | ChangeEvent::Overflow(header) => header,
+        }
+    }
+}
+
+/// Resolves the field bitmaps of a change event header into field names.
+///
+/// A bitmap is either `0x…`, whose bit `i` stands for field `i` of the event
+/// schema, or `{n}-0x…`, whose bit `i` stands for field `i` of the compound
+/// field at position `n`.
+pub fn resolve_field_names(
+    schema: &avro::Schema,
+    record: &Record,
+    bitmaps: &[String],
+) -> Result<Vec<String>, Error> {
+    let mut names = Vec::new();
+    for bitmap in bitmaps {
+        let invalid = |message: String| Error::InvalidBitmap {
+            bitmap: bitmap.clone(),
+            message,
+        };
+        match bitmap.split_once('-') {
+            None => {
+                for index in set_bits(bitmap).map_err(invalid)? {
+                    let field = record
+                        .fields
+                        .get(index)
+                        .ok_or_else(|| invalid(format!("no field at position {index}")))?;
+                    names.push(field.name.clone());
+                }
+            }
+            Some((parent, child_bitmap)) => {
+                let parent = parent
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| record.fields.get(index))
+                    .ok_or_else(|| invalid(format!("no field at position {parent}")))?;
+                let compound = compound_record(schema, &parent.schema)
+                    .ok_or_else(|| invalid(format!("{} is not a compound field", parent.name)))?;
+                for index in set_bits(child_bitmap).map_err(invalid)? {
+                    let field = compound.fields.get(index).ok_or_else(|| {
+                        invalid(format!("no field at position {index} of {}", parent.name))
+                    })?;
+                    names.push(format!("{}.{}", parent.name, field.name));
+                }
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Returns the record type of a compound field, which is usually nullable.
+fn compound_record<'a>(schema: &'a avro::Schema, node: &'a Node) -> Option<&'a Record> {
+    match schema.resolve(node) {
+        Node::Record(record) => Some(record),
+        Node::Union(branches) => branches
+            .iter()
+            .find_map(|branch| compound_record(schema, branch)),
+        _ => None,
+    }
+}
+
+/// Returns the positions of the set bits of a `0x…` bitmap, lowest first.
+fn set_bits(bitmap: &str) -> Result<Vec<usize>, String> {
+    let hex = bitmap
+        .strip_prefix("0x")
+        .ok_or_else(|| "missing 0x prefix".to_string())?;
+    let mut positions = Vec::new();
+    for (nibble_index, digit) in hex.chars().rev().enumerate() {
+        let nibble = digit
+            .to_digit(16)
+            .ok_or_else(|| format!("{digit} is not a hex digit"))?;
+        for bit in 0..4 {
+            if nibble & (1 << bit) != 0 {
+                positions.push(nibble_index * 4 + bit);
+            }
+        }
+    }
+    Ok(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ACCOUNT_CHANGE_EVENT_SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "AccountChangeEvent",
+        "namespace": "com.sforce.eventbus",
+        "fields": [
+            {"name": "ChangeEventHeader", "type": {
+                "type": "record",
+                "name": "ChangeEventHeader",
+                "fields": [
+                    {"name": "entityName", "type": "string"},
+                    {"name": "recordIds", "type": {"type": "array", "items": "string"}},
+                    {"name": "changeType", "type": {
+                        "type": "enum",
+                        "name": "ChangeType",
+                        "symbols": ["CREATE", "UPDATE", "DELETE", "UNDELETE", "GAP_CREATE", "GAP_UPDATE", "GAP_DELETE", "GAP_UNDELETE", "GAP_OVERFLOW"]
+                    }},
+                    {"name": "changeOrigin", "type": "string"},
+                    {"name": "transactionKey", "type": "string"},
+                    {"name": "sequenceNumber", "type": "int"},
+                    {"name": "commitTimestamp", "type": "long"},
+                    {"name": "commitNumber", "type": "long"},
+                    {"name": "commitUser", "type": "string"},
+                    {"name": "nulledFields", "type": {"type": "array", "items": "string"}},
+                    {"name": "diffFields", "type": {"type": "array", "items": "string"}},
+                    {"name": "changedFields", "type": {"type": "array", "items": "string"}}
+                ]
+            }},
+            {"name": "Name", "type": ["null", "string"], "default": null},
+            {"name": "Type", "type": ["null", "string"], "default": null},
+            {"name": "BillingAddress", "type": ["null", {
+                "type": "record",
+                "name": "Address",
+                "fields": [
+                    {"name": "Street", "type": ["null", "string"], "default": null},
+                    {"name": "City", "type": ["null", "string"], "default": null},
+                    {"name": "State", "type": ["null", "string"], "default": null},
+                    {"name": "PostalCode", "type": ["null", "string"], "default": null},
+                    {"name": "Country", "type": ["null", "string"], "default": null}
+                ]
+            }], "default": null},
+            {"name": "Description", "type": ["null", "string"], "default": null},
+            {"name": "LastModifiedDate", "type": ["null", "long"], "default": null}
+        ]
+    }"#;
+
+    /// Encodes an account change event; `header` overrides header fields.
+    fn change_event(header: JsonValue, fields: JsonValue) -> (avro::Schema, Vec<u8>) {
+        let schema = avro::Schema::parse(ACCOUNT_CHANGE_EVENT_SCHEMA).unwrap();
+        let mut json = json!({
+            "ChangeEventHeader": {
+                "entityName": "Account",
+                "recordIds": ["001xx000003DGb2AAG"],
+                "changeType": "UPDATE",
+                "changeOrigin": "com/salesforce/api/soap/61.0;client=SfdcInternalAPI/",
+                "transactionKey": "00051c2e-8a6b-4b2c-9b45-4e1a3b1f8f5c",
+                "sequenceNumber": 1,
+                "commitTimestamp": 1_700_000_000_000_i64,
+                "commitNumber": 11_734_532_781_i64,
+                "commitUser": "005xx000001SwiUAAS",
+                "nulledFields": [],
+                "diffFields": [],
+                "changedFields": []
+            }
+        });
+        json["ChangeEventHeader"]
+            .as_object_mut()
+            .unwrap()
+            .extend(header.as_object().unwrap().clone());
+        json.as_object_mut()
+            .unwrap()
+            .extend(fields.as_object().unwrap().clone());
+        let value = schema.value_from_json(&json).unwrap();
+        let payload = schema.encode(&value).unwrap();
+        (schema, payload)
+    }
+
+    #[test]
+    fn test_update_resolves_bitmaps() {
+        let (schema, payload) = change_event(
+            json!({
+                "nulledFields": ["0x4"],
+                "diffFields": ["0x10"],
+                "changedFields": ["0x2a", "3-0x12"]
+            }),
+            json!({
+                "Name": "Acme",
+                "BillingAddress": {"City": "Paris", "Country": "FR"},
+                "LastModifiedDate": 1_700_000_000_000_i64
+            }),
+        );
+
+        let ChangeEvent::Record(change) = ChangeEvent::decode(&schema, &payload).unwrap() else {
+            panic!("expected a record change");
+        };
+        assert_eq!(change.header.change_type, ChangeType::Update);
+        assert_eq!(change.header.entity_name, "Account");
+        assert_eq!(
+            change.header.commit_time(),
+            UNIX_EPOCH + Duration::from_millis(1_700_000_000_000)
+        );
+        assert_eq!(
+            change.changed_fields,
+            vec![
+                "Name",
+                "BillingAddress",
+                "LastModifiedDate",
+                "BillingAddress.City",
+                "BillingAddress.Country"
+            ]
+        );
+        assert_eq!(change.nulled_fields, vec!["Type"]);
+        assert_eq!(change.diff_fields, vec!["Description"]);
+        assert_eq!(
+            change.field("BillingAddress.City"),
+            Some(&Value::String("Paris".to_string()))
+        );
+        assert_eq!(
+            change.changed_values().next(),
+            Some(("Name", &Value::String("Acme".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_gap_and_overflow_events() {
+        let (schema, payload) = change_event(json!({"changeType": "GAP_UPDATE"}), json!({}));
+        let event = ChangeEvent::decode(&schema, &payload).unwrap();
+        assert!(
+            matches!(&event, ChangeEvent::Gap(header) if header.change_type == ChangeType::GapUpdate)
+        );
+        assert_eq!(event.header().record_ids, vec!["001xx000003DGb2AAG"]);
+
+        let (schema, payload) = change_event(json!({"changeType": "GAP_OVERFLOW"}), json!({}));
+        let event = ChangeEvent::decode(&schema, &payload).unwrap();
+        assert!(matches!(event, ChangeEvent::Overflow(_)));
+        assert!(event.header().change_type.is_gap());
+    }
+
+    #[test]
+    fn test_invalid_bitmaps() {
+        let schema = avro::Schema::parse(ACCOUNT_CHANGE_EVENT_SCHEMA).unwrap();
+        let Node::Record(record) = schema.root() else {
+            panic!("root is not a record");
+        };
+        for bitmap in ["2a", "0xzz", "0x100", "1-0x1", "9-0x1", "3-0x40"] {
+            assert!(
+                matches!(
+                    resolve_field_names(&schema, record, &[bitmap.to_string()]),
+                    Err(Error::InvalidBitmap { .. })
+                ),
+                "{bitmap}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_not_a_change_event() {
+        let schema = avro::Schema::parse(
+            r#"{"type": "record", "name": "Order_Placed__e", "fields": [{"name": "CreatedById", "type": "string"}]}"#,
+        )
+        .unwrap();
+        let payload = schema
+            .encode(&Value::Record(vec![(
+                "CreatedById".to_string(),
+                Value::String("005xx000001SwiUAAS".to_string()),
+            )]))
+            .unwrap();
+        assert!(matches!(
+            ChangeEvent::decode(&schema, &payload),
+            Err(Error::MissingHeader)
+        ));
+    }
+}
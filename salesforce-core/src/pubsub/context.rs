@@ -1,7 +1,21 @@
 use crate::client;
-use oauth2::TokenResponse;
+use crate::http::{self, HttpConfig, TlsRoots};
+use crate::pubsub::error::GrpcError;
+use crate::pubsub::flow::FetchStream;
+use crate::pubsub::managed::ManagedSubscription;
+use crate::pubsub::publish_stream::PublishStream;
+use crate::token::provider::{self, TokenProvider};
 use salesforce_pubsub_v1::eventbus::v1::pub_sub_client::PubSubClient;
-use tokio_stream::StreamExt;
+use std::time::Duration;
+
+/// Default maximum time to establish a connection.
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default interval between HTTP/2 keepalive pings.
+const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default time to wait for a keepalive ping to be acknowledged.
+const DEFAULT_KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Errors that can occur during Pub/Sub operations.
 #[derive(thiserror::Error, Debug)]
@@ -25,10 +39,50 @@ pub enum Error {
     /// gRPC communication error.
     #[error("gRPC transport error: {0}")]
     Tonic(Box<tonic::Status>),
+    /// Refreshing the access token before a call failed.
+    #[error("Failed to refresh access token: {source}")]
+    TokenRefresh {
+        #[source]
+        source: provider::Error,
+    },
+    /// The request stream of a streaming call has already closed.
+    #[error("Request stream closed")]
+    StreamClosed(),
+    /// Failed to configure or connect the gRPC channel.
+    #[error("Failed to connect to Pub/Sub API: {source}")]
+    Channel {
+        #[source]
+        source: http::Error,
+    },
 }
 
+impl Error {
+    /// Returns the classified gRPC failure, if the call reached the server.
+    ///
+    /// The Salesforce `error-code` and `rpc_id` are read from the status
+    /// metadata; see [`GrpcError`].
+    pub fn grpc_error(&self) -> Option<GrpcError> {
+        match self {
+            Error::Tonic(status) => Some(GrpcError::from(status.as_ref())),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the call may succeed if retried.
+    ///
+    /// See [`GrpcError::is_retryable`].
+    pub fn is_retryable(&self) -> bool {
+        self.grpc_error().is_some_and(|error| error.is_retryable())
+    }
+}
+
+/// Adds authentication headers to every Pub/Sub request.
+///
+/// The access token is read from the [`TokenProvider`] on each call so that
+/// refreshed tokens are picked up without rebuilding the [`Context`].
+#[derive(Clone)]
 struct ContextInterceptor {
-    auth_header: tonic::metadata::AsciiMetadataValue,
+    token_provider: TokenProvider,
     instance_url: tonic::metadata::AsciiMetadataValue,
     tenant_id: tonic::metadata::AsciiMetadataValue,
 }
@@ -38,9 +92,12 @@ impl tonic::service::Interceptor for ContextInterceptor {
         &mut self,
         mut request: tonic::Request<()>,
     ) -> Result<tonic::Request<()>, tonic::Status> {
-        request
-            .metadata_mut()
-            .insert("accesstoken", self.auth_header.to_owned());
+        let auth_header: tonic::metadata::AsciiMetadataValue = self
+            .token_provider
+            .access_token()
+            .parse()
+            .map_err(|_| tonic::Status::unauthenticated("Access token is not valid ASCII"))?;
+        request.metadata_mut().insert("accesstoken", auth_header);
         request
             .metadata_mut()
             .insert("instanceurl", self.instance_url.to_owned());
@@ -54,7 +111,11 @@ impl tonic::service::Interceptor for ContextInterceptor {
 /// Pub/Sub API context for making gRPC calls.
 ///
 /// Manages authentication and provides methods for interacting with
-/// Salesforce Pub/Sub API endpoints.
+/// Salesforce Pub/Sub API endpoints. The access token is refreshed before
+/// each call when it is about to expire, so a long-lived context keeps
+/// working across session expiry.
+///
+/// Cloning a context is cheap: clones share the gRPC channel and token.
 ///
 /// # Examples
 ///
@@ -80,7 +141,7 @@ impl tonic::service::Interceptor for ContextInterceptor {
 /// # Ok(())
 /// # }
 /// ```
-#[derive(Debug)]
+#[derive(Clone)]
 pub struct Context {
     pubsub: salesforce_pubsub_v1::eventbus::v1::pub_sub_client::PubSubClient<
         tonic::service::interceptor::InterceptedService<
@@ -88,47 +149,116 @@ pub struct Context {
             ContextInterceptor,
         >,
     >,
+    token_provider: TokenProvider,
+}
+
+impl std::fmt::Debug for Context {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Context")
+            .field("pubsub", &self.pubsub)
+            .field("token_provider", &self.token_provider)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Context {
+    /// Connects to the global Pub/Sub API endpoint with default settings.
+    ///
+    /// Use [`Builder`] to pick another endpoint or tune the connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails or the client is missing
+    /// required authentication data.
+    pub async fn connect(client: client::Client) -> Result<Self, Error> {
+        Builder::new().client(client).connect().await
+    }
+
     /// Creates a new Pub/Sub context.
     ///
     /// # Errors
     ///
     /// Returns an error if the client is missing required authentication data.
     pub fn new(channel: tonic::transport::Channel, client: client::Client) -> Result<Self, Error> {
-        let auth_header: tonic::metadata::AsciiMetadataValue = client
-            .token_result
-            .as_ref()
-            .ok_or_else(Error::MissingTokenResponse)?
+        let instance_url = client.instance_url.clone();
+        let tenant_id = client.tenant_id.clone();
+        let token_provider = TokenProvider::new(client).map_err(|e| match e {
+            provider::Error::MissingTokenResponse() => Error::MissingTokenResponse(),
+            e => Error::TokenRefresh { source: e },
+        })?;
+        Self::build(channel, token_provider, instance_url, tenant_id)
+    }
+
+    /// Creates a new Pub/Sub context that shares an existing [`TokenProvider`].
+    ///
+    /// Use this to run several contexts off one token, or to keep the token
+    /// fresh with [`TokenProvider::spawn_refresh`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the client is missing required authentication data.
+    pub async fn with_token_provider(
+        channel: tonic::transport::Channel,
+        token_provider: TokenProvider,
+    ) -> Result<Self, Error> {
+        let client = token_provider.client().await;
+        Self::build(
+            channel,
+            token_provider,
+            client.instance_url,
+            client.tenant_id,
+        )
+    }
+
+    /// Returns the token provider used to authenticate calls.
+    pub fn token_provider(&self) -> &TokenProvider {
+        &self.token_provider
+    }
+
+    fn build(
+        channel: tonic::transport::Channel,
+        token_provider: TokenProvider,
+        instance_url: Option<String>,
+        tenant_id: Option<String>,
+    ) -> Result<Self, Error> {
+        // Reject tokens that can never be sent as gRPC metadata up front.
+        token_provider
             .access_token()
-            .secret()
-            .parse()
+            .parse::<tonic::metadata::AsciiMetadataValue>()
             .map_err(|e| Error::InvalidMetadataValue { source: e })?;
 
-        let instance_url: tonic::metadata::AsciiMetadataValue = client
-            .instance_url
+        let instance_url: tonic::metadata::AsciiMetadataValue = instance_url
             .as_ref()
             .ok_or_else(|| Error::MissingRequiredAttribute("instance_url".to_string()))?
             .parse()
             .map_err(|e| Error::InvalidMetadataValue { source: e })?;
 
-        let tenant_id: tonic::metadata::AsciiMetadataValue = client
-            .tenant_id
+        let tenant_id: tonic::metadata::AsciiMetadataValue = tenant_id
             .as_ref()
             .ok_or_else(|| Error::MissingRequiredAttribute("tenant_id".to_string()))?
             .parse()
             .map_err(|e| Error::InvalidMetadataValue { source: e })?;
 
         let interceptor = ContextInterceptor {
-            auth_header,
+            token_provider: token_provider.clone(),
             instance_url,
             tenant_id,
         };
 
         let pubsub = PubSubClient::with_interceptor(channel, interceptor);
 
-        Ok(Context { pubsub })
+        Ok(Context {
+            pubsub,
+            token_provider,
+        })
+    }
+
+    /// Refreshes the access token if it is about to expire.
+    async fn ensure_fresh_token(&self) -> Result<(), Error> {
+        self.token_provider
+            .ensure_fresh()
+            .await
+            .map_err(|e| Error::TokenRefresh { source: e })
     }
 
     /// Retrieves topic metadata.
@@ -139,6 +269,7 @@ impl Context {
         &mut self,
         request: salesforce_pubsub_v1::eventbus::v1::TopicRequest,
     ) -> Result<tonic::Response<salesforce_pubsub_v1::eventbus::v1::TopicInfo>, Error> {
+        self.ensure_fresh_token().await?;
         self.pubsub
             .get_topic(tonic::Request::new(request))
             .await
@@ -152,6 +283,7 @@ impl Context {
         &mut self,
         request: salesforce_pubsub_v1::eventbus::v1::SchemaRequest,
     ) -> Result<tonic::Response<salesforce_pubsub_v1::eventbus::v1::SchemaInfo>, Error> {
+        self.ensure_fresh_token().await?;
         self.pubsub
             .get_schema(tonic::Request::new(request))
             .await
@@ -166,6 +298,7 @@ impl Context {
         &mut self,
         request: salesforce_pubsub_v1::eventbus::v1::PublishRequest,
     ) -> Result<tonic::Response<salesforce_pubsub_v1::eventbus::v1::PublishResponse>, Error> {
+        self.ensure_fresh_token().await?;
         self.pubsub
             .publish(tonic::Request::new(request))
             .await
@@ -174,78 +307,302 @@ impl Context {
 
     /// Subscribes to events from a topic.
     ///
-    /// Returns a stream of events. The stream will continue until an error
-    /// occurs or the connection is closed.
+    /// Sends `request` and returns a stream of responses that requests more
+    /// events as they are consumed; see [`FetchStream`] for how credits are
+    /// managed. The stream will continue until an error occurs or the
+    /// connection is closed.
     pub async fn subscribe(
         &mut self,
         request: salesforce_pubsub_v1::eventbus::v1::FetchRequest,
-    ) -> Result<
-        tonic::Response<tonic::codec::Streaming<salesforce_pubsub_v1::eventbus::v1::FetchResponse>>,
-        Error,
-    > {
-        self.pubsub
-            .subscribe(
-                tokio_stream::iter(1..usize::MAX)
-                    .map(move |_| request.to_owned())
-                    .throttle(std::time::Duration::from_millis(10)),
-            )
+    ) -> Result<FetchStream, Error> {
+        self.ensure_fresh_token().await?;
+        let (credits, requests) = FetchStream::start(request);
+        let responses = self
+            .pubsub
+            .subscribe(requests)
             .await
-            .map_err(|e| Error::Tonic(Box::new(e)))
+            .map_err(|e| Error::Tonic(Box::new(e)))?
+            .into_inner();
+        Ok(FetchStream::new(responses, credits))
     }
 
     /// Subscribes to events using a managed subscription.
     ///
     /// Requires a pre-configured managed subscription in Salesforce.
-    /// Returns a stream of events with automatic commit handling.
+    /// Returns a bidirectional handle that tops up credits as responses are
+    /// consumed and commits replay IDs; see [`ManagedSubscription`].
     pub async fn managed_subscribe(
         &mut self,
         request: salesforce_pubsub_v1::eventbus::v1::ManagedFetchRequest,
-    ) -> Result<
-        tonic::Response<
-            tonic::codec::Streaming<salesforce_pubsub_v1::eventbus::v1::ManagedFetchResponse>,
-        >,
-        Error,
-    > {
-        self.pubsub
-            .managed_subscribe(
-                tokio_stream::iter(1..usize::MAX)
-                    .map(move |_| request.to_owned())
-                    .throttle(std::time::Duration::from_millis(10)),
-            )
+    ) -> Result<ManagedSubscription, Error> {
+        self.ensure_fresh_token().await?;
+        let batch_size = request.num_requested;
+        let (committer, requests) = ManagedSubscription::start(request);
+        let responses = self
+            .pubsub
+            .managed_subscribe(requests)
             .await
-            .map_err(|e| Error::Tonic(Box::new(e)))
+            .map_err(|e| Error::Tonic(Box::new(e)))?
+            .into_inner();
+        Ok(ManagedSubscription::new(responses, committer, batch_size))
     }
 
-    /// Publishes events via a bidirectional stream.
+    /// Opens a bidirectional PublishStream call.
     ///
-    /// Allows for continuous publishing with server responses for each batch.
-    /// Useful for high-throughput scenarios.
-    pub async fn publish_stream(
+    /// Returns a handle that sends a new request for each batch of events
+    /// and matches the server's responses back to them; see
+    /// [`PublishStream`]. Useful for high-throughput scenarios.
+    pub async fn publish_stream(&mut self) -> Result<PublishStream, Error> {
+        self.ensure_fresh_token().await?;
+        Ok(PublishStream::start(self.clone()))
+    }
+
+    /// Makes the PublishStream call, sending the requests from `requests`.
+    pub(crate) async fn publish_stream_call(
         &mut self,
-        request: salesforce_pubsub_v1::eventbus::v1::PublishRequest,
-    ) -> Result<
-        tonic::Response<
-            tonic::codec::Streaming<salesforce_pubsub_v1::eventbus::v1::PublishResponse>,
-        >,
-        Error,
-    > {
+        requests: impl tokio_stream::Stream<Item = salesforce_pubsub_v1::eventbus::v1::PublishRequest>
+            + Send
+            + 'static,
+    ) -> Result<tonic::codec::Streaming<salesforce_pubsub_v1::eventbus::v1::PublishResponse>, Error>
+    {
         self.pubsub
-            .publish_stream(
-                tokio_stream::iter(1..usize::MAX)
-                    .map(move |_| request.to_owned())
-                    .throttle(std::time::Duration::from_millis(10)),
-            )
+            .publish_stream(requests)
             .await
+            .map(tonic::Response::into_inner)
             .map_err(|e| Error::Tonic(Box::new(e)))
     }
 }
 
+/// Salesforce Pub/Sub API region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Region {
+    /// The global endpoint, [`eventbus::ENDPOINT`](salesforce_pubsub_v1::eventbus::ENDPOINT).
+    #[default]
+    Global,
+    /// The Hyperforce Germany endpoint,
+    /// [`eventbus::DE_ENDPOINT`](salesforce_pubsub_v1::eventbus::DE_ENDPOINT).
+    Germany,
+}
+
+impl Region {
+    /// Returns the URL of the region's endpoint.
+    pub fn endpoint(&self) -> &'static str {
+        match self {
+            Region::Global => salesforce_pubsub_v1::eventbus::ENDPOINT,
+            Region::Germany => salesforce_pubsub_v1::eventbus::DE_ENDPOINT,
+        }
+    }
+}
+
+/// Builder for a connected [`Context`].
+///
+/// Connects to the endpoint of a [`Region`] or a custom URL. `https`
+/// endpoints use TLS with the operating system's root certificates, or the
+/// roots chosen with [`tls_roots`](Builder::tls_roots), plus any CA
+/// certificates in the [`HttpConfig`], which also supplies the proxy and
+/// `User-Agent`. HTTP/2 keepalive pings are sent every 30 seconds, also
+/// while no call is active, so idle subscriptions are not dropped by
+/// intermediaries.
+///
+/// # Examples
+///
+/// ```no_run
+/// use salesforce_core::client;
+/// use salesforce_core::pubsub::context::{Builder, Region};
+/// use std::path::PathBuf;
+/// use std::time::Duration;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = client::Builder::new()
+///     .credentials_path(PathBuf::from("credentials.json"))
+///     .build()?
+///     .connect()
+///     .await?;
+///
+/// let mut context = Builder::new()
+///     .client(client)
+///     .region(Region::Germany)
+///     .connect_timeout(Duration::from_secs(5))
+///     .max_decoding_message_size(8 * 1024 * 1024)
+///     .connect()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Builder {
+    client: Option<client::Client>,
+    region: Region,
+    endpoint: Option<String>,
+    http_config: Option<HttpConfig>,
+    tls_roots: Option<TlsRoots>,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    keepalive_interval: Option<Duration>,
+    keepalive_timeout: Duration,
+    max_decoding_message_size: Option<usize>,
+    max_encoding_message_size: Option<usize>,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            client: None,
+            region: Region::default(),
+            endpoint: None,
+            http_config: None,
+            tls_roots: None,
+            connect_timeout: None,
+            request_timeout: None,
+            keepalive_interval: Some(DEFAULT_KEEPALIVE_INTERVAL),
+            keepalive_timeout: DEFAULT_KEEPALIVE_TIMEOUT,
+            max_decoding_message_size: None,
+            max_encoding_message_size: None,
+        }
+    }
+}
+
+impl Builder {
+    /// Creates a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the connected client whose access token authenticates calls.
+    pub fn client(mut self, client: client::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Sets the region to connect to. Defaults to [`Region::Global`].
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = region;
+        self
+    }
+
+    /// Connects to `url` instead of the region's endpoint.
+    pub fn endpoint(mut self, url: impl Into<String>) -> Self {
+        self.endpoint = Some(url.into());
+        self
+    }
+
+    /// Sets the proxy, extra CA certificates and `User-Agent`.
+    ///
+    /// Timeouts and TLS roots set on the builder take precedence over the
+    /// config's.
+    pub fn http_config(mut self, http_config: HttpConfig) -> Self {
+        self.http_config = Some(http_config);
+        self
+    }
+
+    /// Sets the root certificates to trust. Defaults to
+    /// [`TlsRoots::Native`], the operating system's certificate store.
+    pub fn tls_roots(mut self, tls_roots: TlsRoots) -> Self {
+        self.tls_roots = Some(tls_roots);
+        self
+    }
+
+    /// Sets the maximum time to establish the connection. Defaults to 10
+    /// seconds.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum time until the response to a call starts.
+    ///
+    /// Streaming calls are not cut off once they are established.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the interval between HTTP/2 keepalive pings, or disables them
+    /// with `None`. Defaults to 30 seconds.
+    pub fn keepalive_interval(mut self, interval: Option<Duration>) -> Self {
+        self.keepalive_interval = interval;
+        self
+    }
+
+    /// Sets how long to wait for a keepalive ping to be acknowledged before
+    /// closing the connection. Defaults to 10 seconds.
+    pub fn keepalive_timeout(mut self, timeout: Duration) -> Self {
+        self.keepalive_timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum size of a response message. Defaults to 4 MiB.
+    pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+        self.max_decoding_message_size = Some(limit);
+        self
+    }
+
+    /// Sets the maximum size of a request message. Defaults to no limit.
+    pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+        self.max_encoding_message_size = Some(limit);
+        self
+    }
+
+    /// Connects to the endpoint and creates the context.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MissingRequiredAttribute`] if no client was set,
+    /// [`Error::Channel`] if the endpoint is invalid or the connection
+    /// fails, or an error if the client is missing required authentication
+    /// data.
+    pub async fn connect(self) -> Result<Context, Error> {
+        let client = self
+            .client
+            .ok_or_else(|| Error::MissingRequiredAttribute("client".to_string()))?;
+        let url = self
+            .endpoint
+            .unwrap_or_else(|| self.region.endpoint().to_string());
+
+        let mut http_config = self.http_config.unwrap_or_default();
+        http_config.connect_timeout = self
+            .connect_timeout
+            .or(http_config.connect_timeout)
+            .or(Some(DEFAULT_CONNECT_TIMEOUT));
+        http_config.timeout = self.request_timeout.or(http_config.timeout);
+        if let Some(tls_roots) = self.tls_roots {
+            http_config.tls_roots = tls_roots;
+        }
+
+        let channel_error = |e| Error::Channel { source: e };
+        let mut endpoint = http_config.endpoint(&url).map_err(channel_error)?;
+        if let Some(interval) = self.keepalive_interval {
+            endpoint = endpoint
+                .http2_keep_alive_interval(interval)
+                .keep_alive_timeout(self.keepalive_timeout)
+                .keep_alive_while_idle(true);
+        }
+        let channel = http_config
+            .connect_endpoint(endpoint)
+            .await
+            .map_err(channel_error)?;
+
+        let mut context = Context::new(channel, client)?;
+        if let Some(limit) = self.max_decoding_message_size {
+            context.pubsub = context.pubsub.max_decoding_message_size(limit);
+        }
+        if let Some(limit) = self.max_encoding_message_size {
+            context.pubsub = context.pubsub.max_encoding_message_size(limit);
+        }
+        Ok(context)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
     use std::{fs, path::PathBuf};
 
     use super::*;
+    use crate::pubsub::testing::{self, FakePubSub};
+    use std::sync::Arc;
     use tonic::service::Interceptor;
 
     #[tokio::test]
@@ -303,8 +660,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_new_missing_instance_url() {
-        use oauth2::basic::BasicTokenResponse;
-        use oauth2::{AccessToken, EmptyExtraTokenFields};
+        use oauth2::AccessToken;
 
         let creds: &str = r#"
             {
@@ -324,10 +680,10 @@ mod tests {
         let _ = fs::remove_file(path);
 
         // Manually set token but not instance_url
-        let token = BasicTokenResponse::new(
+        let token = client::TokenResponse::new(
             AccessToken::new("test_token".to_string()),
             oauth2::basic::BasicTokenType::Bearer,
-            EmptyExtraTokenFields {},
+            Default::default(),
         );
         client.token_result = Some(token);
         client.instance_url = None; // Missing instance_url
@@ -343,8 +699,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_new_missing_tenant_id() {
-        use oauth2::basic::BasicTokenResponse;
-        use oauth2::{AccessToken, EmptyExtraTokenFields};
+        use oauth2::AccessToken;
 
         let creds: &str = r#"
             {
@@ -364,10 +719,10 @@ mod tests {
         let _ = fs::remove_file(path);
 
         // Manually set token and instance_url but not tenant_id
-        let token = BasicTokenResponse::new(
+        let token = client::TokenResponse::new(
             AccessToken::new("test_token".to_string()),
             oauth2::basic::BasicTokenType::Bearer,
-            EmptyExtraTokenFields {},
+            Default::default(),
         );
         client.token_result = Some(token);
         client.instance_url = Some("https://mydomain.salesforce.com".to_string());
@@ -383,8 +738,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_context_debug_impl() {
-        use oauth2::basic::BasicTokenResponse;
-        use oauth2::{AccessToken, EmptyExtraTokenFields};
+        use oauth2::AccessToken;
 
         let creds: &str = r#"
             {
@@ -403,10 +757,10 @@ mod tests {
             .unwrap();
         let _ = fs::remove_file(path);
 
-        let token = BasicTokenResponse::new(
+        let token = client::TokenResponse::new(
             AccessToken::new("test_token".to_string()),
             oauth2::basic::BasicTokenType::Bearer,
-            EmptyExtraTokenFields {},
+            Default::default(),
         );
         client.token_result = Some(token);
         client.instance_url = Some("https://mydomain.salesforce.com".to_string());
@@ -418,29 +772,30 @@ mod tests {
         let context = Context::new(channel, client).unwrap();
         let debug_str = format!("{context:?}");
         assert!(debug_str.contains("Context"));
+        assert!(!debug_str.contains("test_token"));
     }
 
     #[tokio::test]
     async fn test_new_with_valid_client() {
-        use oauth2::basic::BasicTokenResponse;
-        use oauth2::{AccessToken, EmptyExtraTokenFields};
+        use oauth2::AccessToken;
 
         let mut client = client::Builder::new()
             .credentials(client::Credentials {
                 client_id: "test_id".to_string(),
-                client_secret: Some("test_secret".to_string()),
+                client_secret: Some("test_secret".into()),
                 username: None,
                 password: None,
                 instance_url: "https://test.salesforce.com".to_string(),
-                tenant_id: "test_tenant".to_string(),
+                tenant_id: Some("test_tenant".to_string()),
+                ..Default::default()
             })
             .build()
             .unwrap();
 
-        let token = BasicTokenResponse::new(
+        let token = client::TokenResponse::new(
             AccessToken::new("valid_token".to_string()),
             oauth2::basic::BasicTokenType::Bearer,
-            EmptyExtraTokenFields {},
+            Default::default(),
         );
         client.token_result = Some(token);
         client.instance_url = Some("https://test.salesforce.com".to_string());
@@ -455,26 +810,26 @@ mod tests {
 
     #[tokio::test]
     async fn test_new_with_invalid_token_characters() {
-        use oauth2::basic::BasicTokenResponse;
-        use oauth2::{AccessToken, EmptyExtraTokenFields};
+        use oauth2::AccessToken;
 
         let mut client = client::Builder::new()
             .credentials(client::Credentials {
                 client_id: "test_id".to_string(),
-                client_secret: Some("test_secret".to_string()),
+                client_secret: Some("test_secret".into()),
                 username: None,
                 password: None,
                 instance_url: "https://test.salesforce.com".to_string(),
-                tenant_id: "test_tenant".to_string(),
+                tenant_id: Some("test_tenant".to_string()),
+                ..Default::default()
             })
             .build()
             .unwrap();
 
         // Token with newline character (invalid ASCII for metadata)
-        let token = BasicTokenResponse::new(
+        let token = client::TokenResponse::new(
             AccessToken::new("token\nwith\nnewlines".to_string()),
             oauth2::basic::BasicTokenType::Bearer,
-            EmptyExtraTokenFields {},
+            Default::default(),
         );
         client.token_result = Some(token);
         client.instance_url = Some("https://test.salesforce.com".to_string());
@@ -489,25 +844,25 @@ mod tests {
 
     #[tokio::test]
     async fn test_new_with_invalid_instance_url_characters() {
-        use oauth2::basic::BasicTokenResponse;
-        use oauth2::{AccessToken, EmptyExtraTokenFields};
+        use oauth2::AccessToken;
 
         let mut client = client::Builder::new()
             .credentials(client::Credentials {
                 client_id: "test_id".to_string(),
-                client_secret: Some("test_secret".to_string()),
+                client_secret: Some("test_secret".into()),
                 username: None,
                 password: None,
                 instance_url: "https://test.salesforce.com".to_string(),
-                tenant_id: "test_tenant".to_string(),
+                tenant_id: Some("test_tenant".to_string()),
+                ..Default::default()
             })
             .build()
             .unwrap();
 
-        let token = BasicTokenResponse::new(
+        let token = client::TokenResponse::new(
             AccessToken::new("valid_token".to_string()),
             oauth2::basic::BasicTokenType::Bearer,
-            EmptyExtraTokenFields {},
+            Default::default(),
         );
         client.token_result = Some(token);
         client.instance_url = Some("url\nwith\nnewlines".to_string());
@@ -522,25 +877,25 @@ mod tests {
 
     #[tokio::test]
     async fn test_new_with_invalid_tenant_id_characters() {
-        use oauth2::basic::BasicTokenResponse;
-        use oauth2::{AccessToken, EmptyExtraTokenFields};
+        use oauth2::AccessToken;
 
         let mut client = client::Builder::new()
             .credentials(client::Credentials {
                 client_id: "test_id".to_string(),
-                client_secret: Some("test_secret".to_string()),
+                client_secret: Some("test_secret".into()),
                 username: None,
                 password: None,
                 instance_url: "https://test.salesforce.com".to_string(),
-                tenant_id: "test_tenant".to_string(),
+                tenant_id: Some("test_tenant".to_string()),
+                ..Default::default()
             })
             .build()
             .unwrap();
 
-        let token = BasicTokenResponse::new(
+        let token = client::TokenResponse::new(
             AccessToken::new("valid_token".to_string()),
             oauth2::basic::BasicTokenType::Bearer,
-            EmptyExtraTokenFields {},
+            Default::default(),
         );
         client.token_result = Some(token);
         client.instance_url = Some("https://test.salesforce.com".to_string());
@@ -582,15 +937,42 @@ mod tests {
         assert!(format!("{error}").contains("gRPC transport error"));
     }
 
+    #[test]
+    fn test_error_grpc_error_classification() {
+        let error = Error::Tonic(Box::new(tonic::Status::resource_exhausted("too many")));
+        assert!(matches!(error.grpc_error(), Some(GrpcError::Throttled(_))));
+        assert!(error.is_retryable());
+
+        let error = Error::MissingClient();
+        assert!(error.grpc_error().is_none());
+        assert!(!error.is_retryable());
+    }
+
+    fn token_provider(access_token: &str) -> TokenProvider {
+        use oauth2::AccessToken;
+
+        let mut client = client::Builder::new()
+            .credentials_path(PathBuf::from("/tmp/test.json"))
+            .build()
+            .unwrap();
+        client.token_result = Some(client::TokenResponse::new(
+            AccessToken::new(access_token.to_string()),
+            oauth2::basic::BasicTokenType::Bearer,
+            Default::default(),
+        ));
+        client.instance_url = Some("https://test.salesforce.com".to_string());
+        client.tenant_id = Some("test_tenant".to_string());
+        TokenProvider::new(client).unwrap()
+    }
+
     #[test]
     fn test_interceptor_adds_headers() {
-        let auth_header = tonic::metadata::AsciiMetadataValue::try_from("test_token").unwrap();
         let instance_url =
             tonic::metadata::AsciiMetadataValue::try_from("https://test.salesforce.com").unwrap();
         let tenant_id = tonic::metadata::AsciiMetadataValue::try_from("test_tenant").unwrap();
 
         let mut interceptor = ContextInterceptor {
-            auth_header,
+            token_provider: token_provider("test_token"),
             instance_url,
             tenant_id,
         };
@@ -612,27 +994,27 @@ mod tests {
 
     #[tokio::test]
     async fn test_context_creation_success_path() {
-        use oauth2::basic::BasicTokenResponse;
-        use oauth2::{AccessToken, EmptyExtraTokenFields};
+        use oauth2::AccessToken;
 
         // Create a client with all valid data
         let mut client = client::Builder::new()
             .credentials(client::Credentials {
                 client_id: "client123".to_string(),
-                client_secret: Some("secret123".to_string()),
+                client_secret: Some("secret123".into()),
                 username: None,
                 password: None,
                 instance_url: "https://login.salesforce.com".to_string(),
-                tenant_id: "00Dxx0000001gPL".to_string(),
+                tenant_id: Some("00Dxx0000001gPL".to_string()),
+                ..Default::default()
             })
             .build()
             .unwrap();
 
         // Set valid token and metadata
-        let token = BasicTokenResponse::new(
+        let token = client::TokenResponse::new(
             AccessToken::new("valid_access_token_123".to_string()),
             oauth2::basic::BasicTokenType::Bearer,
-            EmptyExtraTokenFields {},
+            Default::default(),
         );
         client.token_result = Some(token);
         client.instance_url = Some("https://login.salesforce.com".to_string());
@@ -655,14 +1037,15 @@ mod tests {
 
     #[test]
     fn test_credentials_from_value_variant() {
-        let creds_from = client::CredentialsFrom::Value(client::Credentials {
+        let creds_from = client::CredentialsFrom::Value(Box::new(client::Credentials {
             client_id: "test".to_string(),
-            client_secret: Some("secret".to_string()),
+            client_secret: Some("secret".into()),
             username: None,
             password: None,
             instance_url: "https://test.salesforce.com".to_string(),
-            tenant_id: "tenant".to_string(),
-        });
+            tenant_id: Some("tenant".to_string()),
+            ..Default::default()
+        }));
 
         match creds_from {
             client::CredentialsFrom::Value(creds) => {
@@ -706,26 +1089,26 @@ mod tests {
 
     #[tokio::test]
     async fn test_context_with_special_characters_in_token() {
-        use oauth2::basic::BasicTokenResponse;
-        use oauth2::{AccessToken, EmptyExtraTokenFields};
+        use oauth2::AccessToken;
 
         let mut client = client::Builder::new()
             .credentials(client::Credentials {
                 client_id: "test".to_string(),
-                client_secret: Some("secret".to_string()),
+                client_secret: Some("secret".into()),
                 username: None,
                 password: None,
                 instance_url: "https://test.salesforce.com".to_string(),
-                tenant_id: "tenant".to_string(),
+                tenant_id: Some("tenant".to_string()),
+                ..Default::default()
             })
             .build()
             .unwrap();
 
         // Token with special characters that are valid ASCII
-        let token = BasicTokenResponse::new(
+        let token = client::TokenResponse::new(
             AccessToken::new("abc123-xyz_789.token".to_string()),
             oauth2::basic::BasicTokenType::Bearer,
-            EmptyExtraTokenFields {},
+            Default::default(),
         );
         client.token_result = Some(token);
         client.instance_url = Some("https://test.salesforce.com".to_string());
@@ -744,11 +1127,12 @@ mod tests {
         let client = client::Builder::new()
             .credentials(client::Credentials {
                 client_id: "test".to_string(),
-                client_secret: Some("secret".to_string()),
+                client_secret: Some("secret".into()),
                 username: None,
                 password: None,
                 instance_url: "https://test.salesforce.com".to_string(),
-                tenant_id: "tenant".to_string(),
+                tenant_id: Some("tenant".to_string()),
+                ..Default::default()
             })
             .build()
             .unwrap();
@@ -760,4 +1144,135 @@ mod tests {
         let result = Context::new(channel, client);
         assert!(matches!(result, Err(Error::MissingTokenResponse())));
     }
+
+    #[tokio::test]
+    async fn test_interceptor_reads_refreshed_token() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(wiremock::ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"access_token": "token_1", "token_type": "Bearer"}),
+            ))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(wiremock::ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"access_token": "token_2", "token_type": "Bearer"}),
+            ))
+            .mount(&server)
+            .await;
+
+        let client = client::Builder::new()
+            .credentials(client::Credentials {
+                client_id: "test_id".to_string(),
+                client_secret: Some("test_secret".into()),
+                instance_url: server.uri(),
+                tenant_id: Some("test_tenant".to_string()),
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let token_provider = TokenProvider::new(client).unwrap();
+
+        let channel =
+            tonic::transport::Endpoint::from_static("http://localhost:50051").connect_lazy();
+        let context = Context::with_token_provider(channel, token_provider.clone())
+            .await
+            .unwrap();
+        let mut interceptor = ContextInterceptor {
+            token_provider: context.token_provider().clone(),
+            instance_url: tonic::metadata::AsciiMetadataValue::try_from("https://test").unwrap(),
+            tenant_id: tonic::metadata::AsciiMetadataValue::try_from("tenant").unwrap(),
+        };
+
+        let request = interceptor.call(tonic::Request::new(())).unwrap();
+        assert_eq!(request.metadata().get("accesstoken").unwrap(), "token_1");
+
+        token_provider.refresh().await.unwrap();
+
+        let request = interceptor.call(tonic::Request::new(())).unwrap();
+        assert_eq!(request.metadata().get("accesstoken").unwrap(), "token_2");
+    }
+
+    #[test]
+    fn test_error_display_token_refresh() {
+        let error = Error::TokenRefresh {
+            source: provider::Error::MissingTokenResponse(),
+        };
+        assert!(error.to_string().contains("Failed to refresh access token"));
+    }
+
+    #[test]
+    fn test_region_endpoints() {
+        assert_eq!(
+            Region::default().endpoint(),
+            salesforce_pubsub_v1::eventbus::ENDPOINT
+        );
+        assert_eq!(
+            Region::Germany.endpoint(),
+            salesforce_pubsub_v1::eventbus::DE_ENDPOINT
+        );
+    }
+
+    #[tokio::test]
+    async fn test_builder_missing_client() {
+        let result = Builder::new().connect().await;
+        assert!(matches!(
+            result,
+            Err(Error::MissingRequiredAttribute(attribute)) if attribute == "client"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_builder_invalid_endpoint() {
+        let result = Builder::new()
+            .client(testing::connected_client())
+            .endpoint("not a url")
+            .connect()
+            .await;
+        assert!(matches!(result, Err(Error::Channel { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_builder_connects_to_custom_endpoint() {
+        let fake = Arc::new(FakePubSub::default().with_topic("/event/Order__e", "schema-1"));
+        let mut context = Builder::new()
+            .client(testing::connected_client())
+            .endpoint(testing::listen(fake).await)
+            .request_timeout(Duration::from_secs(5))
+            .connect()
+            .await
+            .unwrap();
+
+        let topic = context
+            .get_topic(salesforce_pubsub_v1::eventbus::v1::TopicRequest {
+                topic_name: "/event/Order__e".to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(topic.schema_id, "schema-1");
+    }
+
+    #[tokio::test]
+    async fn test_builder_max_decoding_message_size() {
+        let fake = Arc::new(FakePubSub::default().with_topic("/event/Order__e", "schema-1"));
+        let mut context = Builder::new()
+            .client(testing::connected_client())
+            .endpoint(testing::listen(fake).await)
+            .max_decoding_message_size(8)
+            .connect()
+            .await
+            .unwrap();
+
+        let result = context
+            .get_topic(salesforce_pubsub_v1::eventbus::v1::TopicRequest {
+                topic_name: "/event/Order__e".to_string(),
+            })
+            .await;
+        assert!(matches!(result, Err(Error::Tonic(_))));
+    }
 }
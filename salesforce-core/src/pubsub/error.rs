@@ -0,0 +1,339 @@
+/// Metadata key of the Salesforce error code on failed calls.
+const ERROR_CODE_KEY: &str = "error-code";
+
+/// Metadata keys the Salesforce RPC ID may be sent under.
+const RPC_ID_KEYS: [&str; 2] = ["rpc-id", "rpc_id"];
+
+/// Details of a failed Pub/Sub call.
+///
+/// Carries the gRPC status together with the Salesforce `error-code`, such
+/// as `sfdc.platform.eventbus.grpc.subscription.fetch.replayid.corrupted`,
+/// and the `rpc_id` Salesforce support needs to trace the call.
+#[derive(Debug, Clone)]
+pub struct ErrorDetails {
+    status: tonic::Status,
+    error_code: Option<String>,
+    rpc_id: Option<String>,
+}
+
+impl ErrorDetails {
+    /// Returns the gRPC status of the call.
+    pub fn status(&self) -> &tonic::Status {
+        &self.status
+    }
+
+    /// Returns the Salesforce error code, if the server sent one.
+    pub fn error_code(&self) -> Option<&str> {
+        self.error_code.as_deref()
+    }
+
+    /// Returns the ID of the failed call, if the server sent one.
+    pub fn rpc_id(&self) -> Option<&str> {
+        self.rpc_id.as_deref()
+    }
+}
+
+impl std::fmt::Display for ErrorDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.status.code(), self.status.message())?;
+        if let Some(error_code) = &self.error_code {
+            write!(f, " (error code {error_code})")?;
+        }
+        if let Some(rpc_id) = &self.rpc_id {
+            write!(f, " (RPC ID {rpc_id})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Classified failure of a Pub/Sub gRPC call.
+///
+/// Built from a [`tonic::Status`] by reading the Salesforce `error-code`
+/// and `rpc_id` from its metadata, falling back to the gRPC status code
+/// when no error code was sent.
+///
+/// # Examples
+///
+/// ```
+/// use salesforce_core::pubsub::error::GrpcError;
+///
+/// let mut status = tonic::Status::invalid_argument("The replay ID is invalid");
+/// status.metadata_mut().insert(
+///     "error-code",
+///     "sfdc.platform.eventbus.grpc.subscription.fetch.replayid.corrupted"
+///         .parse()
+///         .unwrap(),
+/// );
+/// status.metadata_mut().insert("rpc-id", "a1b2c3".parse().unwrap());
+///
+/// let error = GrpcError::from(&status);
+/// assert!(matches!(error, GrpcError::InvalidReplayId(_)));
+/// assert_eq!(error.details().rpc_id(), Some("a1b2c3"));
+/// assert!(!error.is_retryable());
+/// ```
+#[derive(thiserror::Error, Debug, Clone)]
+#[non_exhaustive]
+pub enum GrpcError {
+    /// The access token expired or was rejected.
+    #[error("Session expired or invalid: {0}")]
+    AuthExpired(Box<ErrorDetails>),
+    /// The user lacks permission for the topic or operation.
+    #[error("Permission denied: {0}")]
+    PermissionDenied(Box<ErrorDetails>),
+    /// The replay ID is malformed or outside the retention window.
+    #[error("Invalid replay ID: {0}")]
+    InvalidReplayId(Box<ErrorDetails>),
+    /// The topic or managed subscription does not exist.
+    #[error("Topic not found: {0}")]
+    TopicNotFound(Box<ErrorDetails>),
+    /// The request was rejected as invalid.
+    #[error("Invalid request: {0}")]
+    InvalidRequest(Box<ErrorDetails>),
+    /// A limit was exceeded.
+    #[error("Throttled: {0}")]
+    Throttled(Box<ErrorDetails>),
+    /// The service is temporarily unavailable or the connection dropped.
+    #[error("Service unavailable: {0}")]
+    Unavailable(Box<ErrorDetails>),
+    /// Any other failure.
+    #[error("gRPC error: {0}")]
+    Other(Box<ErrorDetails>),
+}
+
+impl GrpcError {
+    /// Returns the status, error code and RPC ID of the failed call.
+    pub fn details(&self) -> &ErrorDetails {
+        match self {
+            GrpcError::AuthExpired(details)
+            | GrpcError::PermissionDenied(details)
+            | GrpcError::InvalidReplayId(details)
+            | GrpcError::TopicNotFound(details)
+            | GrpcError::InvalidRequest(details)
+            | GrpcError::Throttled(details)
+            | GrpcError::Unavailable(details)
+            | GrpcError::Other(details) => details,
+        }
+    }
+
+    /// Returns whether the call may succeed if retried.
+    ///
+    /// Throttling and unavailability are retryable after a backoff, and an
+    /// expired session after refreshing the access token. Invalid replay
+    /// IDs, missing topics, missing permissions and invalid requests are
+    /// not.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            GrpcError::AuthExpired(_) | GrpcError::Throttled(_) | GrpcError::Unavailable(_)
+        )
+    }
+}
+
+impl From<&tonic::Status> for GrpcError {
+    fn from(status: &tonic::Status) -> Self {
+        let metadata = status.metadata();
+        let error_code = metadata
+            .get(ERROR_CODE_KEY)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let rpc_id = RPC_ID_KEYS
+            .iter()
+            .find_map(|key| metadata.get(*key))
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let constructor = error_code
+            .as_deref()
+            .and_then(classify_error_code)
+            .unwrap_or_else(|| classify_status(status));
+        constructor(Box::new(ErrorDetails {
+            status: status.clone(),
+            error_code,
+            rpc_id,
+        }))
+    }
+}
+
+impl From<tonic::Status> for GrpcError {
+    fn from(status: tonic::Status) -> Self {
+        Self::from(&status)
+    }
+}
+
+type Constructor = fn(Box<ErrorDetails>) -> GrpcError;
+
+/// Documented Salesforce error codes and the failures they stand for.
+///
+/// Codes not listed here are classified by their gRPC status code.
+const ERROR_CODES: [(&str, Constructor); 4] = [
+    (
+        "sfdc.platform.eventbus.grpc.service.auth.error",
+        GrpcError::AuthExpired,
+    ),
+    (
+        "sfdc.platform.eventbus.grpc.subscription.fetch.replayid.corrupted",
+        GrpcError::InvalidReplayId,
+    ),
+    (
+        "sfdc.platform.eventbus.grpc.subscription.fetch.replayid.validation.failed",
+        GrpcError::InvalidReplayId,
+    ),
+    (
+        "sfdc.platform.eventbus.grpc.subscription.limit.exceeded",
+        GrpcError::Throttled,
+    ),
+];
+
+/// Classifies a Salesforce error code, or returns `None` if it is not
+/// recognized.
+fn classify_error_code(error_code: &str) -> Option<Constructor> {
+    ERROR_CODES
+        .iter()
+        .find(|(code, _)| *code == error_code)
+        .map(|(_, constructor)| *constructor)
+}
+
+/// Classifies a status by its gRPC code.
+fn classify_status(status: &tonic::Status) -> Constructor {
+    use tonic::Code;
+
+    match status.code() {
+        Code::Unauthenticated => GrpcError::AuthExpired,
+        Code::PermissionDenied => GrpcError::PermissionDenied,
+        Code::NotFound => GrpcError::TopicNotFound,
+        Code::ResourceExhausted => GrpcError::Throttled,
+        Code::InvalidArgument if status.message().to_lowercase().contains("replay") => {
+            GrpcError::InvalidReplayId
+        }
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+            GrpcError::InvalidRequest
+        }
+        Code::Unavailable
+        | Code::Aborted
+        | Code::Internal
+        | Code::Unknown
+        | Code::DeadlineExceeded
+        | Code::Cancelled => GrpcError::Unavailable,
+        _ => GrpcError::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_error_code(mut status: tonic::Status, error_code: &str) -> tonic::Status {
+        status
+            .metadata_mut()
+            .insert(ERROR_CODE_KEY, error_code.parse().unwrap());
+        status
+    }
+
+    #[test]
+    fn test_classifies_error_codes() {
+        let cases = [
+            (
+                "sfdc.platform.eventbus.grpc.subscription.fetch.replayid.corrupted",
+                "InvalidReplayId",
+            ),
+            (
+                "sfdc.platform.eventbus.grpc.subscription.fetch.replayid.validation.failed",
+                "InvalidReplayId",
+            ),
+            (
+                "sfdc.platform.eventbus.grpc.service.auth.error",
+                "AuthExpired",
+            ),
+            (
+                "sfdc.platform.eventbus.grpc.subscription.limit.exceeded",
+                "Throttled",
+            ),
+        ];
+        for (error_code, variant) in cases {
+            let status = with_error_code(tonic::Status::internal("failed"), error_code);
+            let error = GrpcError::from(&status);
+            assert!(format!("{error:?}").starts_with(variant), "{error_code}");
+            assert_eq!(error.details().error_code(), Some(error_code));
+        }
+    }
+
+    #[test]
+    fn test_unlisted_error_codes_use_status_code() {
+        // Codes that merely contain words like "auth" or "access" must not
+        // be mistaken for an expired session.
+        let status = with_error_code(
+            tonic::Status::permission_denied("no access"),
+            "sfdc.platform.eventbus.grpc.topic.authorization.failed",
+        );
+        assert!(matches!(
+            GrpcError::from(&status),
+            GrpcError::PermissionDenied(_)
+        ));
+        let status = with_error_code(
+            tonic::Status::unavailable("try again"),
+            "sfdc.platform.eventbus.grpc.service.accesstoken.lookup",
+        );
+        assert!(matches!(
+            GrpcError::from(&status),
+            GrpcError::Unavailable(_)
+        ));
+    }
+
+    #[test]
+    fn test_falls_back_to_status_code() {
+        let unknown = with_error_code(
+            tonic::Status::unauthenticated("expired"),
+            "sfdc.platform.eventbus.grpc.something.new",
+        );
+        assert!(matches!(
+            GrpcError::from(&unknown),
+            GrpcError::AuthExpired(_)
+        ));
+        assert!(matches!(
+            GrpcError::from(tonic::Status::not_found("")),
+            GrpcError::TopicNotFound(_)
+        ));
+        assert!(matches!(
+            GrpcError::from(tonic::Status::invalid_argument("bad replay ID")),
+            GrpcError::InvalidReplayId(_)
+        ));
+        assert!(matches!(
+            GrpcError::from(tonic::Status::invalid_argument("bad topic name")),
+            GrpcError::InvalidRequest(_)
+        ));
+        assert!(matches!(
+            GrpcError::from(tonic::Status::deadline_exceeded("")),
+            GrpcError::Unavailable(_)
+        ));
+        assert!(matches!(
+            GrpcError::from(tonic::Status::already_exists("")),
+            GrpcError::Other(_)
+        ));
+    }
+
+    #[test]
+    fn test_retryable_classification() {
+        assert!(GrpcError::from(tonic::Status::unavailable("")).is_retryable());
+        assert!(GrpcError::from(tonic::Status::resource_exhausted("")).is_retryable());
+        assert!(GrpcError::from(tonic::Status::unauthenticated("")).is_retryable());
+        assert!(!GrpcError::from(tonic::Status::permission_denied("")).is_retryable());
+        assert!(!GrpcError::from(tonic::Status::not_found("")).is_retryable());
+    }
+
+    #[test]
+    fn test_display_includes_error_code_and_rpc_id() {
+        let mut status = with_error_code(
+            tonic::Status::unauthenticated("session expired"),
+            "sfdc.platform.eventbus.grpc.service.auth.error",
+        );
+        status
+            .metadata_mut()
+            .insert("rpc-id", "rpc-123".parse().unwrap());
+        let error = GrpcError::from(&status);
+        assert_eq!(error.details().rpc_id(), Some("rpc-123"));
+        assert_eq!(
+            error.to_string(),
+            "Session expired or invalid: Unauthenticated: session expired \
+             (error code sfdc.platform.eventbus.grpc.service.auth.error) (RPC ID rpc-123)"
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use crate::pubsub::context::Error;
+use futures_util::Stream;
+use salesforce_pubsub_v1::eventbus::v1::{FetchRequest, FetchResponse};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll};
+use tokio::sync::mpsc;
+
+/// Counts events requested but not yet delivered on a streaming call.
+///
+/// The count is kept locally from the requests sent and events received.
+/// When the server reports fewer pending credits and no request is in
+/// flight, the server's figure is used.
+#[derive(Debug, Default)]
+pub(crate) struct CreditCounter {
+    /// Events requested but not yet delivered.
+    pending: i32,
+    /// Whether credits were requested since the last response arrived.
+    requested_since_response: bool,
+}
+
+impl CreditCounter {
+    /// Returns the number of events requested but not yet received.
+    pub(crate) fn pending(&self) -> i32 {
+        self.pending
+    }
+
+    /// Records a request for `num_requested` more events.
+    pub(crate) fn requested(&mut self, num_requested: i32) {
+        self.pending = self.pending.saturating_add(num_requested);
+        self.requested_since_response = true;
+    }
+
+    /// Records a response carrying `events` events and reporting
+    /// `server_pending` outstanding credits, and returns the new count.
+    pub(crate) fn received(&mut self, events: usize, server_pending: i32) -> i32 {
+        let events = i32::try_from(events).unwrap_or(i32::MAX);
+        self.pending = self.pending.saturating_sub(events).max(0);
+        if !self.requested_since_response {
+            self.pending = self.pending.min(server_pending.max(0));
+        }
+        self.requested_since_response = false;
+        self.pending
+    }
+
+    /// Returns how many credits to request to get back to `batch_size`, or
+    /// `None` if the count has not dropped below `threshold`.
+    pub(crate) fn top_up(&self, batch_size: i32, threshold: Option<i32>) -> Option<i32> {
+        threshold
+            .filter(|threshold| self.pending < *threshold)
+            .map(|_| batch_size - self.pending)
+            .filter(|num_requested| *num_requested > 0)
+    }
+}
+
+/// Handle for requesting more events on a Subscribe stream.
+///
+/// Obtained from [`FetchStream::credits`]. Handles can be cloned and moved
+/// to other tasks; requests go out on the stream they were obtained from.
+#[derive(Debug, Clone)]
+pub struct Credits {
+    requests: mpsc::UnboundedSender<FetchRequest>,
+    topic_name: String,
+    state: Arc<Mutex<CreditCounter>>,
+}
+
+impl Credits {
+    /// Asks the server for `num_requested` more events.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::StreamClosed`] if the Subscribe stream has ended.
+    pub fn request_more(&self, num_requested: i32) -> Result<(), Error> {
+        if num_requested <= 0 {
+            return Ok(());
+        }
+        self.send(FetchRequest {
+            topic_name: self.topic_name.clone(),
+            num_requested,
+            ..Default::default()
+        })
+    }
+
+    /// Returns the number of events requested but not yet received.
+    pub fn pending(&self) -> i32 {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pending()
+    }
+
+    fn send(&self, request: FetchRequest) -> Result<(), Error> {
+        let num_requested = request.num_requested;
+        self.requests
+            .send(request)
+            .map_err(|_| Error::StreamClosed())?;
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .requested(num_requested);
+        Ok(())
+    }
+}
+
+/// Subscribe stream with credit-based flow control.
+///
+/// The server delivers only as many events as have been requested. The
+/// stream sends the initial [`FetchRequest`] and then, as responses are
+/// consumed, tops the pending credits back up to the initial
+/// `num_requested` whenever they drop below a threshold. The threshold
+/// defaults to half of `num_requested`; set it with
+/// [`top_up_threshold`](FetchStream::top_up_threshold), or disable
+/// automatic top-ups and call [`Credits::request_more`] instead.
+///
+/// Pending credits are counted locally from the requests sent and events
+/// received. When the server reports fewer pending credits in
+/// `pending_num_requested` and no request is in flight, the server's figure
+/// is used.
+///
+/// # Examples
+///
+/// ```no_run
+/// use futures_util::StreamExt;
+/// use salesforce_core::pubsub::context::Context;
+/// use salesforce_pubsub_v1::eventbus::v1::FetchRequest;
+///
+/// # async fn example(mut context: Context) -> Result<(), Box<dyn std::error::Error>> {
+/// let mut stream = context
+///     .subscribe(FetchRequest {
+///         topic_name: "/event/Order__e".to_string(),
+///         num_requested: 10,
+///         ..Default::default()
+///     })
+///     .await?
+///     .top_up_threshold(None);
+/// let credits = stream.credits();
+///
+/// while let Some(response) = stream.next().await {
+///     let response = response?;
+///     // Process the events, then ask for the next batch.
+///     credits.request_more(response.events.len() as i32)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FetchStream {
+    responses: tonic::Streaming<FetchResponse>,
+    credits: Credits,
+    batch_size: i32,
+    top_up_threshold: Option<i32>,
+}
+
+impl FetchStream {
+    /// Sends `request` on a new request stream and returns the sender half
+    /// together with the stream the gRPC call should consume.
+    pub(crate) fn start(
+        request: FetchRequest,
+    ) -> (
+        Credits,
+        tokio_stream::wrappers::UnboundedReceiverStream<FetchRequest>,
+    ) {
+        let (requests, receiver) = mpsc::unbounded_channel();
+        let credits = Credits {
+            requests,
+            topic_name: request.topic_name.clone(),
+            state: Arc::default(),
+        };
+        // The receiver is alive, so the first request cannot fail.
+        let _ = credits.send(request);
+        (
+            credits,
+            tokio_stream::wrappers::UnboundedReceiverStream::new(receiver),
+        )
+    }
+
+    pub(crate) fn new(responses: tonic::Streaming<FetchResponse>, credits: Credits) -> Self {
+        let batch_size = credits.pending();
+        Self {
+            responses,
+            credits,
+            batch_size,
+            top_up_threshold: Some(batch_size / 2),
+        }
+    }
+
+    /// Tops up credits when fewer than `threshold` events are pending, or
+    /// never with `None`.
+    pub fn top_up_threshold(mut self, threshold: Option<i32>) -> Self {
+        self.top_up_threshold = threshold;
+        self
+    }
+
+    /// Returns a handle for requesting more events.
+    pub fn credits(&self) -> Credits {
+        self.credits.clone()
+    }
+
+    /// Returns the next response, or `None` when the server ends the stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns the status the stream failed with.
+    pub async fn message(&mut self) -> Result<Option<FetchResponse>, tonic::Status> {
+        futures_util::StreamExt::next(self).await.transpose()
+    }
+
+    /// Updates the credit count for `response` and tops it up if needed.
+    fn on_response(&self, response: &FetchResponse) {
+        let top_up = {
+            let mut state = self.credits.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.received(response.events.len(), response.pending_num_requested);
+            state.top_up(self.batch_size, self.top_up_threshold)
+        };
+        if let Some(num_requested) = top_up {
+            // A closed request stream surfaces as the end of the responses.
+            let _ = self.credits.request_more(num_requested);
+        }
+    }
+}
+
+impl Stream for FetchStream {
+    type Item = Result<FetchResponse, tonic::Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let item = std::task::ready!(Pin::new(&mut self.responses).poll_next(cx));
+        if let Some(Ok(response)) = &item {
+            self.on_response(response);
+        }
+        Poll::Ready(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pubsub::testing::{self, FakePubSub};
+    use futures_util::StreamExt;
+    use salesforce_pubsub_v1::eventbus::v1::{ConsumerEvent, FetchRequest, FetchResponse};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const TOPIC: &str = "/event/Order__e";
+
+    fn response(events: usize, pending_num_requested: i32) -> FetchResponse {
+        FetchResponse {
+            events: vec![ConsumerEvent::default(); events],
+            pending_num_requested,
+            ..Default::default()
+        }
+    }
+
+    fn request(num_requested: i32) -> FetchRequest {
+        FetchRequest {
+            topic_name: TOPIC.to_string(),
+            num_requested,
+            ..Default::default()
+        }
+    }
+
+    /// Waits until the fake server has seen `count` top-up requests.
+    async fn top_up_requests(fake: &FakePubSub, count: usize) -> Vec<FetchRequest> {
+        for _ in 0..50 {
+            let requests = fake.top_up_requests.lock().unwrap().clone();
+            if requests.len() >= count {
+                return requests;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        fake.top_up_requests.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn test_tops_up_below_threshold() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.push_session(vec![Ok(response(3, 7)), Ok(response(4, 3))]);
+        let mut context = testing::serve(fake.clone()).await;
+        let mut stream = context.subscribe(request(10)).await.unwrap();
+        let credits = stream.credits();
+
+        stream.next().await.unwrap().unwrap();
+        assert_eq!(credits.pending(), 7);
+        stream.next().await.unwrap().unwrap();
+
+        assert_eq!(fake.fetch_requests.lock().unwrap()[0].num_requested, 10);
+        let top_ups = top_up_requests(&fake, 1).await;
+        assert_eq!(top_ups.len(), 1);
+        assert_eq!(top_ups[0].num_requested, 7);
+        assert_eq!(top_ups[0].topic_name, TOPIC);
+        assert_eq!(credits.pending(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_manual_request_more() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.push_session(vec![Ok(response(5, 0))]);
+        let mut context = testing::serve(fake.clone()).await;
+        let mut stream = context
+            .subscribe(request(5))
+            .await
+            .unwrap()
+            .top_up_threshold(None);
+        let credits = stream.credits();
+
+        stream.next().await.unwrap().unwrap();
+        assert_eq!(credits.pending(), 0);
+        assert!(top_up_requests(&fake, 1).await.is_empty());
+
+        credits.request_more(3).unwrap();
+        assert_eq!(credits.pending(), 3);
+        let top_ups = top_up_requests(&fake, 1).await;
+        assert_eq!(top_ups[0].num_requested, 3);
+    }
+
+    #[tokio::test]
+    async fn test_server_pending_lowers_local_count() {
+        let fake = Arc::new(FakePubSub::default());
+        // Keepalives: the first arrives while the initial request is still
+        // counted as in flight, the second reports fewer pending credits.
+        fake.push_session(vec![Ok(response(0, 10)), Ok(response(0, 8))]);
+        let mut context = testing::serve(fake.clone()).await;
+        let mut stream = context
+            .subscribe(request(10))
+            .await
+            .unwrap()
+            .top_up_threshold(None);
+        let credits = stream.credits();
+
+        stream.next().await.unwrap().unwrap();
+        assert_eq!(credits.pending(), 10);
+        stream.next().await.unwrap().unwrap();
+        assert_eq!(credits.pending(), 8);
+    }
+}
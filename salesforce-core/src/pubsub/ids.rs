@@ -0,0 +1,18 @@
+/// Returns a random UUID (version 4) to use as a `ProducerEvent.id` or
+/// commit request ID.
+pub(crate) fn new_event_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_event_id_is_uuid_v4() {
+        let id = new_event_id();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert_ne!(id, new_event_id());
+    }
+}
@@ -0,0 +1,506 @@
+use crate::pubsub::context;
+use crate::pubsub::flow::CreditCounter;
+use crate::pubsub::ids::new_event_id;
+use futures_util::Stream;
+use salesforce_pubsub_v1::eventbus::v1::{
+    CommitReplayRequest, CommitReplayResponse, ErrorCode, ManagedFetchRequest, ManagedFetchResponse,
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll};
+use tokio::sync::{mpsc, oneshot};
+
+/// Errors that can occur on a managed subscription.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The ManagedSubscribe stream failed.
+    #[error("Managed subscription failed: {source}")]
+    Subscribe {
+        #[source]
+        source: context::Error,
+    },
+    /// The server rejected a commit.
+    #[error("Commit {commit_request_id} failed ({}): {message}", code.as_str_name())]
+    Commit {
+        /// ID of the rejected commit request.
+        commit_request_id: String,
+        /// Replay ID that was to be committed.
+        replay_id: Vec<u8>,
+        /// Error code reported by the server.
+        code: ErrorCode,
+        /// Error message reported by the server.
+        message: String,
+    },
+    /// The stream ended before the server answered a commit.
+    #[error("Stream ended before commit {commit_request_id} was acknowledged")]
+    CommitAborted {
+        /// ID of the unanswered commit request.
+        commit_request_id: String,
+    },
+}
+
+/// Who is waiting for a commit response.
+#[derive(Debug)]
+enum Waiter {
+    /// A caller of [`Committer::commit`].
+    Caller(oneshot::Sender<Result<CommitReplayResponse, Error>>),
+    /// An automatic commit; failures are reported on the stream.
+    Auto,
+}
+
+/// Handle for committing replay IDs on a managed subscription.
+///
+/// Obtained from [`ManagedSubscription::committer`]. Handles can be cloned
+/// and moved to other tasks.
+#[derive(Debug, Clone)]
+pub struct Committer {
+    requests: mpsc::UnboundedSender<ManagedFetchRequest>,
+    waiters: Arc<Mutex<HashMap<String, Waiter>>>,
+    subscription_id: String,
+    developer_name: String,
+}
+
+impl Committer {
+    /// Commits `replay_id` as the position of the managed subscription.
+    ///
+    /// The request is sent right away. The returned future resolves when
+    /// the server answers with the matching `commit_request_id`, and does
+    /// not need to be awaited for the commit to take effect.
+    ///
+    /// # Errors
+    ///
+    /// The future resolves to [`Error::Commit`] if the server rejects the
+    /// commit, or [`Error::CommitAborted`] if the stream ends first.
+    pub fn commit(
+        &self,
+        replay_id: Vec<u8>,
+    ) -> impl Future<Output = Result<CommitReplayResponse, Error>> + Send + 'static {
+        let (sender, receiver) = oneshot::channel();
+        let commit_request_id = self.send(replay_id, Waiter::Caller(sender));
+        async move {
+            receiver
+                .await
+                .unwrap_or(Err(Error::CommitAborted { commit_request_id }))
+        }
+    }
+
+    /// Sends a commit request and registers `waiter` for its response.
+    fn send(&self, replay_id: Vec<u8>, waiter: Waiter) -> String {
+        let commit_request_id = new_event_id();
+        self.waiters
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(commit_request_id.clone(), waiter);
+        let request = ManagedFetchRequest {
+            subscription_id: self.subscription_id.clone(),
+            developer_name: self.developer_name.clone(),
+            commit_replay_id_request: Some(CommitReplayRequest {
+                commit_request_id: commit_request_id.clone(),
+                replay_id,
+            }),
+            ..Default::default()
+        };
+        if self.requests.send(request).is_err() {
+            // Dropping the waiter resolves the caller with CommitAborted.
+            self.waiters
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&commit_request_id);
+        }
+        commit_request_id
+    }
+}
+
+/// Bidirectional handle for a managed subscription.
+///
+/// The stream yields the responses of the ManagedSubscribe call. Like
+/// [`FetchStream`](crate::pubsub::flow::FetchStream), it tops up credits as
+/// responses are consumed; see
+/// [`top_up_threshold`](ManagedSubscription::top_up_threshold).
+///
+/// Commit replay IDs with [`commit`](ManagedSubscription::commit), or
+/// enable [`auto_commit`](ManagedSubscription::auto_commit) to commit the
+/// last replay ID of each batch once the next one is requested. A batch
+/// that was yielded but not followed by another request is not committed,
+/// so dropping the subscription mid-batch redelivers it. Failed automatic
+/// commits are yielded as [`Error::Commit`] items.
+///
+/// # Examples
+///
+/// ```no_run
+/// use futures_util::StreamExt;
+/// use salesforce_core::pubsub::context::Context;
+/// use salesforce_pubsub_v1::eventbus::v1::ManagedFetchRequest;
+///
+/// # async fn example(mut context: Context) -> Result<(), Box<dyn std::error::Error>> {
+/// let mut subscription = context
+///     .managed_subscribe(ManagedFetchRequest {
+///         developer_name: "Order_Subscription".to_string(),
+///         num_requested: 100,
+///         ..Default::default()
+///     })
+///     .await?;
+///
+/// while let Some(response) = subscription.next().await {
+///     let response = response?;
+///     if let Some(last) = response.events.last() {
+///         // Process the batch, then record the position.
+///         subscription.commit(last.replay_id.clone()).await?;
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ManagedSubscription {
+    responses: mpsc::UnboundedReceiver<Result<ManagedFetchResponse, Error>>,
+    committer: Committer,
+    credits: CreditCounter,
+    batch_size: i32,
+    top_up_threshold: Option<i32>,
+    auto_commit: bool,
+    uncommitted: Option<Vec<u8>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl ManagedSubscription {
+    /// Sends `request` on a new request stream and returns the sender half
+    /// together with the stream the gRPC call should consume.
+    pub(crate) fn start(
+        request: ManagedFetchRequest,
+    ) -> (
+        Committer,
+        tokio_stream::wrappers::UnboundedReceiverStream<ManagedFetchRequest>,
+    ) {
+        let (requests, receiver) = mpsc::unbounded_channel();
+        let committer = Committer {
+            requests,
+            waiters: Arc::default(),
+            subscription_id: request.subscription_id.clone(),
+            developer_name: request.developer_name.clone(),
+        };
+        // The receiver is alive, so the first request cannot fail.
+        let _ = committer.requests.send(request);
+        (
+            committer,
+            tokio_stream::wrappers::UnboundedReceiverStream::new(receiver),
+        )
+    }
+
+    pub(crate) fn new(
+        mut responses: tonic::Streaming<ManagedFetchResponse>,
+        committer: Committer,
+        batch_size: i32,
+    ) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let waiters = committer.waiters.clone();
+        // Reads responses independently of the caller, so commit futures
+        // resolve even while the stream is not being polled. Memory stays
+        // bounded because the server sends no more events than requested.
+        let task = tokio::spawn(async move {
+            loop {
+                let item = match responses.message().await {
+                    Ok(Some(mut response)) => {
+                        if let Some(commit_response) = response.commit_response.take() {
+                            if let Some(error) = resolve_commit(&waiters, commit_response) {
+                                let _ = sender.send(Err(error));
+                            }
+                            if response.events.is_empty() {
+                                continue;
+                            }
+                        }
+                        Ok(response)
+                    }
+                    Ok(None) => break,
+                    Err(status) => Err(Error::Subscribe {
+                        source: context::Error::Tonic(Box::new(status)),
+                    }),
+                };
+                let failed = item.is_err();
+                if sender.send(item).is_err() || failed {
+                    break;
+                }
+            }
+            // Unanswered commits resolve with CommitAborted.
+            waiters.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        });
+
+        let mut credits = CreditCounter::default();
+        credits.requested(batch_size);
+        Self {
+            responses: receiver,
+            committer,
+            credits,
+            batch_size,
+            top_up_threshold: Some(batch_size / 2),
+            auto_commit: false,
+            uncommitted: None,
+            task,
+        }
+    }
+
+    /// Tops up credits when fewer than `threshold` events are pending, or
+    /// never with `None`.
+    pub fn top_up_threshold(mut self, threshold: Option<i32>) -> Self {
+        self.top_up_threshold = threshold;
+        self
+    }
+
+    /// Commits the last replay ID of each batch when the next response is
+    /// requested.
+    pub fn auto_commit(mut self, auto_commit: bool) -> Self {
+        self.auto_commit = auto_commit;
+        self
+    }
+
+    /// Returns a handle for committing replay IDs from other tasks.
+    pub fn committer(&self) -> Committer {
+        self.committer.clone()
+    }
+
+    /// Commits `replay_id`; see [`Committer::commit`].
+    pub fn commit(
+        &self,
+        replay_id: Vec<u8>,
+    ) -> impl Future<Output = Result<CommitReplayResponse, Error>> + Send + 'static {
+        self.committer.commit(replay_id)
+    }
+
+    /// Asks the server for `num_requested` more events.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`context::Error::StreamClosed`] if the stream has ended.
+    pub fn request_more(&mut self, num_requested: i32) -> Result<(), context::Error> {
+        if num_requested <= 0 {
+            return Ok(());
+        }
+        self.committer
+            .requests
+            .send(ManagedFetchRequest {
+                subscription_id: self.committer.subscription_id.clone(),
+                developer_name: self.committer.developer_name.clone(),
+                num_requested,
+                ..Default::default()
+            })
+            .map_err(|_| context::Error::StreamClosed())?;
+        self.credits.requested(num_requested);
+        Ok(())
+    }
+
+    /// Returns the number of events requested but not yet received.
+    pub fn pending(&self) -> i32 {
+        self.credits.pending()
+    }
+}
+
+impl Stream for ManagedSubscription {
+    type Item = Result<ManagedFetchResponse, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if let Some(replay_id) = this.uncommitted.take() {
+            this.committer.send(replay_id, Waiter::Auto);
+        }
+
+        let item = std::task::ready!(this.responses.poll_recv(cx));
+        if let Some(Ok(response)) = &item {
+            this.credits
+                .received(response.events.len(), response.pending_num_requested);
+            if let Some(num_requested) = this.credits.top_up(this.batch_size, this.top_up_threshold)
+            {
+                // A closed request stream surfaces as the end of the responses.
+                let _ = this.request_more(num_requested);
+            }
+            if this.auto_commit {
+                if let Some(last) = response.events.last() {
+                    this.uncommitted = Some(last.replay_id.clone());
+                }
+            }
+        }
+        Poll::Ready(item)
+    }
+}
+
+impl Drop for ManagedSubscription {
+    fn drop(&mut self) {
+        // The last yielded batch may not have been processed, so its replay
+        // ID is left uncommitted for the server to redeliver.
+        self.task.abort();
+    }
+}
+
+/// Resolves the waiter for `response`, returning the error of a failed
+/// automatic commit.
+fn resolve_commit(
+    waiters: &Mutex<HashMap<String, Waiter>>,
+    response: CommitReplayResponse,
+) -> Option<Error> {
+    let waiter = waiters
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&response.commit_request_id);
+    let result = match &response.error {
+        Some(error) => Err(Error::Commit {
+            commit_request_id: response.commit_request_id.clone(),
+            replay_id: response.replay_id.clone(),
+            code: ErrorCode::try_from(error.code).unwrap_or(ErrorCode::Unknown),
+            message: error.msg.clone(),
+        }),
+        None => Ok(response),
+    };
+    match (waiter, result) {
+        (Some(Waiter::Caller(sender)), result) => {
+            let _ = sender.send(result);
+            None
+        }
+        (Some(Waiter::Auto), Err(error)) => Some(error),
+        (Some(Waiter::Auto), Ok(_)) => None,
+        (None, result) => {
+            tracing::debug!("Ignoring response for unknown commit: {result:?}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::testing::{self, FakePubSub};
+    use futures_util::StreamExt;
+    use salesforce_pubsub_v1::eventbus::v1::ConsumerEvent;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn batch(replay_ids: &[u8]) -> ManagedFetchResponse {
+        ManagedFetchResponse {
+            events: replay_ids
+                .iter()
+                .map(|replay_id| ConsumerEvent {
+                    event: None,
+                    replay_id: vec![*replay_id],
+                })
+                .collect(),
+            pending_num_requested: 10,
+            ..Default::default()
+        }
+    }
+
+    async fn subscribe(fake: &Arc<FakePubSub>) -> ManagedSubscription {
+        let mut context = testing::serve(fake.clone()).await;
+        context
+            .managed_subscribe(ManagedFetchRequest {
+                developer_name: "Order_Subscription".to_string(),
+                num_requested: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+    }
+
+    /// Waits until the fake server has seen `count` commit requests.
+    async fn commits(fake: &FakePubSub, count: usize) -> Vec<CommitReplayRequest> {
+        for _ in 0..50 {
+            let commits: Vec<_> = fake
+                .managed_requests
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|request| request.commit_replay_id_request.clone())
+                .collect();
+            if commits.len() >= count {
+                return commits;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("expected {count} commit requests");
+    }
+
+    #[tokio::test]
+    async fn test_commit_resolves_matching_response() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.push_managed_session(vec![batch(&[1, 2])]);
+        let mut subscription = subscribe(&fake).await;
+
+        let response = subscription.next().await.unwrap().unwrap();
+        assert_eq!(response.events.len(), 2);
+        let committed = subscription.commit(vec![2]).await.unwrap();
+        assert_eq!(committed.replay_id, vec![2]);
+
+        let commits = commits(&fake, 1).await;
+        assert_eq!(commits[0].commit_request_id, committed.commit_request_id);
+        assert!(!commits[0].commit_request_id.is_empty());
+        let first = fake.managed_requests.lock().unwrap()[0].clone();
+        assert_eq!(first.developer_name, "Order_Subscription");
+        assert_eq!(first.num_requested, 10);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_commit_is_typed_error() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.rejected_commits.lock().unwrap().push(vec![9]);
+        let subscription = subscribe(&fake).await;
+
+        let error = subscription.commit(vec![9]).await.unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Commit { code: ErrorCode::Commit, ref replay_id, .. } if *replay_id == vec![9]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_auto_commit_after_batch() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.rejected_commits.lock().unwrap().push(vec![4]);
+        fake.push_managed_session(vec![batch(&[1, 2]), batch(&[3, 4]), batch(&[5])]);
+        let mut subscription = subscribe(&fake).await.auto_commit(true);
+
+        subscription.next().await.unwrap().unwrap();
+        subscription.next().await.unwrap().unwrap();
+        assert_eq!(commits(&fake, 1).await[0].replay_id, vec![2]);
+
+        // The rejected commit of the second batch is reported on the stream.
+        let mut items = Vec::new();
+        for _ in 0..2 {
+            items.push(subscription.next().await.unwrap());
+        }
+        assert!(items.iter().any(
+            |item| matches!(item, Err(Error::Commit { replay_id, .. }) if *replay_id == vec![4])
+        ));
+        assert_eq!(commits(&fake, 2).await[1].replay_id, vec![4]);
+    }
+
+    #[tokio::test]
+    async fn test_drop_does_not_commit_unprocessed_batch() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.push_managed_session(vec![batch(&[1, 2])]);
+        let mut subscription = subscribe(&fake).await.auto_commit(true);
+
+        subscription.next().await.unwrap().unwrap();
+        drop(subscription);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let requests = fake.managed_requests.lock().unwrap();
+        assert!(requests
+            .iter()
+            .all(|request| request.commit_replay_id_request.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_pending_commit_aborted_when_stream_fails() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.push_managed_session(vec![]);
+        fake.fail_managed_commits
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        let mut subscription = subscribe(&fake).await;
+
+        let commit = subscription.commit(vec![1]);
+        assert!(matches!(
+            subscription.next().await,
+            Some(Err(Error::Subscribe { .. }))
+        ));
+        assert!(matches!(commit.await, Err(Error::CommitAborted { .. })));
+    }
+}
@@ -0,0 +1,368 @@
+use crate::pubsub::context;
+use crate::pubsub::ids::new_event_id;
+use salesforce_pubsub_v1::eventbus::v1::{PublishRequest, PublishResponse};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll};
+use tokio::sync::{mpsc, oneshot};
+
+/// Number of requests that can be queued before [`PublishStream::send`]
+/// waits.
+const REQUEST_BUFFER: usize = 16;
+
+/// Errors that can occur on a [`PublishStream`].
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The PublishStream call failed.
+    #[error("Publish stream failed: {source}")]
+    Stream {
+        #[source]
+        source: Arc<context::Error>,
+    },
+    /// The stream ended before the server answered the request.
+    #[error("Publish stream closed before the request was acknowledged")]
+    Closed,
+}
+
+/// Future resolving to the server's response to one sent request.
+///
+/// Returned by [`PublishStream::send`]. The request is published whether
+/// or not the future is awaited.
+#[derive(Debug)]
+pub struct PublishAck {
+    response: oneshot::Receiver<Result<PublishResponse, Error>>,
+}
+
+impl Future for PublishAck {
+    type Output = Result<PublishResponse, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.response)
+            .poll(cx)
+            .map(|response| response.unwrap_or(Err(Error::Closed)))
+    }
+}
+
+/// Requests sent but not yet answered.
+#[derive(Debug, Default)]
+struct Outstanding {
+    /// Senders for the response to each request, by send number.
+    acks: HashMap<u64, oneshot::Sender<Result<PublishResponse, Error>>>,
+    /// Send numbers by event ID, for matching responses.
+    by_event_id: HashMap<String, u64>,
+    /// Event IDs of each request, by send number.
+    event_ids: HashMap<u64, Vec<String>>,
+    next_send: u64,
+    /// Error the stream failed with, once it has ended.
+    failed: Option<Arc<context::Error>>,
+    ended: bool,
+}
+
+impl Outstanding {
+    /// Resolves the request that `response` answers.
+    ///
+    /// Responses are matched through the `correlation_key` of their first
+    /// result. Responses that match no unanswered request are dropped.
+    fn resolve(&mut self, response: PublishResponse) {
+        let send = response
+            .results
+            .first()
+            .and_then(|result| self.by_event_id.get(&result.correlation_key).copied());
+        let Some(send) = send else {
+            tracing::warn!("Ignoring unmatched publish response {}", response.rpc_id);
+            return;
+        };
+        if let Some(ack) = self.remove(send) {
+            let _ = ack.send(Ok(response));
+        }
+    }
+
+    /// Forgets the request with send number `send` and returns the sender
+    /// for its response.
+    fn remove(&mut self, send: u64) -> Option<oneshot::Sender<Result<PublishResponse, Error>>> {
+        for event_id in self.event_ids.remove(&send).unwrap_or_default() {
+            self.by_event_id.remove(&event_id);
+        }
+        self.acks.remove(&send)
+    }
+
+    /// Marks the stream as ended and fails every unanswered request.
+    fn end(&mut self, failed: Option<context::Error>) {
+        self.ended = true;
+        self.failed = failed.map(Arc::new);
+        for (_, ack) in std::mem::take(&mut self.acks) {
+            let _ = ack.send(Err(self.error()));
+        }
+        self.by_event_id.clear();
+        self.event_ids.clear();
+    }
+
+    fn error(&self) -> Error {
+        match &self.failed {
+            Some(source) => Error::Stream {
+                source: source.clone(),
+            },
+            None => Error::Closed,
+        }
+    }
+}
+
+/// Long-lived bidirectional PublishStream call.
+///
+/// Each [`send`](PublishStream::send) puts a new [`PublishRequest`] on the
+/// stream and returns a [`PublishAck`] for the server's response to it.
+/// Responses are matched to requests through the `correlation_key` of
+/// their results, so events without an ID get a random one.
+///
+/// [`close`](PublishStream::close) half-closes the stream and waits until
+/// the server has answered everything sent. Dropping the handle half-closes
+/// the stream too; requests already sent are still delivered and their acks
+/// still resolve.
+///
+/// # Examples
+///
+/// ```no_run
+/// use salesforce_core::pubsub::context::Context;
+/// use salesforce_pubsub_v1::eventbus::v1::{ProducerEvent, PublishRequest};
+///
+/// # async fn example(mut context: Context, events: Vec<ProducerEvent>) -> Result<(), Box<dyn std::error::Error>> {
+/// let stream = context.publish_stream().await?;
+///
+/// let mut acks = Vec::new();
+/// for chunk in events.chunks(100) {
+///     let request = PublishRequest {
+///         topic_name: "/event/Order__e".to_string(),
+///         events: chunk.to_vec(),
+///         ..Default::default()
+///     };
+///     acks.push(stream.send(request).await?);
+/// }
+/// stream.close().await?;
+/// for ack in acks {
+///     println!("Published {} events", ack.await?.results.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PublishStream {
+    requests: mpsc::Sender<PublishRequest>,
+    outstanding: Arc<Mutex<Outstanding>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl PublishStream {
+    /// Starts the call in a background task and returns its handle.
+    pub(crate) fn start(mut context: context::Context) -> Self {
+        let (requests, receiver) = mpsc::channel(REQUEST_BUFFER);
+        let outstanding = Arc::new(Mutex::new(Outstanding::default()));
+        let task = tokio::spawn({
+            let outstanding = outstanding.clone();
+            async move {
+                let requests = tokio_stream::wrappers::ReceiverStream::new(receiver);
+                let mut responses = match context.publish_stream_call(requests).await {
+                    Ok(responses) => responses,
+                    Err(e) => return outstanding.lock().unwrap().end(Some(e)),
+                };
+                let failed = loop {
+                    match responses.message().await {
+                        Ok(Some(response)) => outstanding.lock().unwrap().resolve(response),
+                        Ok(None) => break None,
+                        Err(status) => break Some(context::Error::Tonic(Box::new(status))),
+                    }
+                };
+                outstanding.lock().unwrap().end(failed);
+            }
+        });
+        Self {
+            requests,
+            outstanding,
+            task,
+        }
+    }
+
+    /// Sends `request` on the stream.
+    ///
+    /// Waits while too many requests are queued, then returns a
+    /// [`PublishAck`] for the server's response.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream has already ended.
+    pub async fn send(&self, mut request: PublishRequest) -> Result<PublishAck, Error> {
+        for event in &mut request.events {
+            if event.id.is_empty() {
+                event.id = new_event_id();
+            }
+        }
+        let (sender, receiver) = oneshot::channel();
+        let send = {
+            let mut outstanding = self.outstanding.lock().unwrap();
+            if outstanding.ended {
+                return Err(outstanding.error());
+            }
+            let send = outstanding.next_send;
+            outstanding.next_send += 1;
+            let event_ids: Vec<_> = request.events.iter().map(|e| e.id.clone()).collect();
+            for event_id in &event_ids {
+                outstanding.by_event_id.insert(event_id.clone(), send);
+            }
+            outstanding.event_ids.insert(send, event_ids);
+            outstanding.acks.insert(send, sender);
+            send
+        };
+
+        if self.requests.send(request).await.is_err() {
+            let mut outstanding = self.outstanding.lock().unwrap();
+            outstanding.remove(send);
+            return Err(outstanding.error());
+        }
+        Ok(PublishAck { response: receiver })
+    }
+
+    /// Half-closes the stream and waits for the responses to everything
+    /// sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream failed.
+    pub async fn close(self) -> Result<(), Error> {
+        drop(self.requests);
+        let _ = self.task.await;
+        match &self.outstanding.lock().unwrap().failed {
+            Some(source) => Err(Error::Stream {
+                source: source.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::testing::{self, FakePubSub};
+    use salesforce_pubsub_v1::eventbus::v1::{ProducerEvent, PublishResult};
+
+    const TOPIC: &str = "/event/Order__e";
+
+    fn request(ids: &[&str]) -> PublishRequest {
+        PublishRequest {
+            topic_name: TOPIC.to_string(),
+            events: ids
+                .iter()
+                .map(|id| ProducerEvent {
+                    id: id.to_string(),
+                    schema_id: "schema-1".to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn correlation_keys(response: &PublishResponse) -> Vec<String> {
+        response
+            .results
+            .iter()
+            .map(|result| result.correlation_key.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_acks_match_sends() {
+        let fake = Arc::new(FakePubSub::default());
+        let mut context = testing::serve(fake.clone()).await;
+        let stream = context.publish_stream().await.unwrap();
+
+        let first = stream.send(request(&["a", "b"])).await.unwrap();
+        let second = stream.send(request(&["c"])).await.unwrap();
+        assert_eq!(correlation_keys(&second.await.unwrap()), vec!["c"]);
+        assert_eq!(correlation_keys(&first.await.unwrap()), vec!["a", "b"]);
+        stream.close().await.unwrap();
+
+        let published = fake.stream_published.lock().unwrap();
+        assert_eq!(published.len(), 2);
+        assert_eq!(published[1].events[0].id, "c");
+    }
+
+    #[tokio::test]
+    async fn test_close_waits_for_outstanding_acks() {
+        let fake = Arc::new(FakePubSub::default());
+        let mut context = testing::serve(fake.clone()).await;
+        let stream = context.publish_stream().await.unwrap();
+
+        let ack = stream.send(request(&[""])).await.unwrap();
+        stream.close().await.unwrap();
+        let response = ack.await.unwrap();
+        assert_eq!(response.results[0].correlation_key.len(), 36);
+    }
+
+    #[tokio::test]
+    async fn test_drop_flushes_sent_requests() {
+        let fake = Arc::new(FakePubSub::default());
+        let mut context = testing::serve(fake.clone()).await;
+        let stream = context.publish_stream().await.unwrap();
+
+        let ack = stream.send(request(&["a"])).await.unwrap();
+        drop(stream);
+        assert!(ack.await.is_ok());
+        assert_eq!(fake.stream_published.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_remove_forgets_request() {
+        let mut outstanding = Outstanding::default();
+        let (sender, _receiver) = oneshot::channel();
+        outstanding.acks.insert(0, sender);
+        outstanding.by_event_id.insert("a".to_string(), 0);
+        outstanding.event_ids.insert(0, vec!["a".to_string()]);
+
+        assert!(outstanding.remove(0).is_some());
+        assert!(outstanding.acks.is_empty());
+        assert!(outstanding.by_event_id.is_empty());
+        assert!(outstanding.event_ids.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_ignores_unmatched_response() {
+        let mut outstanding = Outstanding::default();
+        let (sender, mut receiver) = oneshot::channel();
+        outstanding.acks.insert(0, sender);
+        outstanding.by_event_id.insert("a".to_string(), 0);
+        outstanding.event_ids.insert(0, vec!["a".to_string()]);
+
+        outstanding.resolve(PublishResponse {
+            results: vec![PublishResult {
+                correlation_key: "b".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        outstanding.resolve(PublishResponse::default());
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(outstanding.acks.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_call_fails_sends() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.publish_failures
+            .lock()
+            .unwrap()
+            .push_back(tonic::Status::permission_denied("no access"));
+        let mut context = testing::serve(fake.clone()).await;
+        let stream = context.publish_stream().await.unwrap();
+
+        let result = match stream.send(request(&["a"])).await {
+            Ok(ack) => ack.await,
+            Err(e) => Err(e),
+        };
+        assert!(matches!(result, Err(Error::Stream { .. })));
+        assert!(matches!(stream.close().await, Err(Error::Stream { .. })));
+        assert!(fake.stream_published.lock().unwrap().is_empty());
+    }
+}
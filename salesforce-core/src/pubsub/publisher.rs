@@ -0,0 +1,694 @@
+use crate::pubsub::context::{self, Context};
+use crate::pubsub::error::GrpcError;
+use crate::pubsub::ids::new_event_id;
+use crate::pubsub::retry::RetryPolicy;
+use prost::Message as _;
+use salesforce_pubsub_v1::eventbus::v1::{ErrorCode, ProducerEvent, PublishRequest, PublishResult};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+/// Default maximum number of events per Publish request.
+pub const DEFAULT_MAX_EVENTS: usize = 200;
+
+/// Default maximum encoded size of the events in one Publish request.
+///
+/// Stays below the 4 MB gRPC message limit with room for request overhead.
+pub const DEFAULT_MAX_BYTES: usize = 3 * 1024 * 1024;
+
+/// Default time an incomplete batch waits for more events.
+pub const DEFAULT_LINGER: Duration = Duration::from_millis(20);
+
+/// Default number of times failed events are resent.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Errors that can occur while publishing through a [`Publisher`].
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Required builder attribute is missing.
+    #[error("Missing required attribute: {}", _0)]
+    MissingRequiredAttribute(String),
+    /// The event alone exceeds the batch size limit.
+    #[error("Event {event_id} is {size} bytes, more than the limit of {max_bytes}")]
+    EventTooLarge {
+        /// ID of the event.
+        event_id: String,
+        /// Encoded size of the event.
+        size: usize,
+        /// Configured batch size limit.
+        max_bytes: usize,
+    },
+    /// The Publish call for the batch containing the event failed.
+    #[error("Failed to publish to {topic_name}: {source}")]
+    Publish {
+        /// Name of the topic.
+        topic_name: String,
+        #[source]
+        source: Arc<context::Error>,
+    },
+    /// The server rejected the event.
+    #[error("Event {event_id} rejected ({}): {message}", code.as_str_name())]
+    Rejected {
+        /// ID of the event.
+        event_id: String,
+        /// Error code reported by the server.
+        code: ErrorCode,
+        /// Error message reported by the server.
+        message: String,
+    },
+    /// The server returned no result for the event.
+    #[error("No publish result for event {event_id}")]
+    MissingResult {
+        /// ID of the event.
+        event_id: String,
+    },
+    /// The publisher stopped before the event was published.
+    #[error("Publisher closed")]
+    Closed,
+}
+
+impl Error {
+    /// Returns whether publishing the event again may succeed.
+    ///
+    /// [Retryable](GrpcError::is_retryable) call failures such as
+    /// throttling or expired sessions, results with [`ErrorCode::Publish`]
+    /// and missing results are retryable. Invalid requests, missing permissions and
+    /// oversized events are not.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Publish { source, .. } => source.is_retryable(),
+            Error::Rejected { code, .. } => *code == ErrorCode::Publish,
+            Error::MissingResult { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// Future resolving to the replay ID of a published event.
+///
+/// Returned by [`Publisher::publish`]. The event is published whether or
+/// not the future is awaited.
+#[derive(Debug)]
+pub struct Delivery {
+    result: oneshot::Receiver<Result<Vec<u8>, Error>>,
+}
+
+impl Future for Delivery {
+    type Output = Result<Vec<u8>, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.result)
+            .poll(cx)
+            .map(|result| result.unwrap_or(Err(Error::Closed)))
+    }
+}
+
+/// Event waiting to be published, with the sender for its result.
+#[derive(Debug)]
+struct Queued {
+    event: ProducerEvent,
+    size: usize,
+    result: oneshot::Sender<Result<Vec<u8>, Error>>,
+}
+
+/// Publishes individual events in size-limited batches.
+///
+/// Events are grouped into Publish requests of at most
+/// [`max_events`](Builder::max_events) events and
+/// [`max_bytes`](Builder::max_bytes) encoded bytes. A batch is sent when it
+/// is full, or when its first event has waited for
+/// [`linger`](Builder::linger). Batches are sent one at a time, in order.
+///
+/// Each event's result is matched back through its `correlation_key`.
+/// Events without an ID get a random one. Dropping every clone of the
+/// publisher flushes the events already queued.
+///
+/// Events that fail with a [retryable](Error::is_retryable) error are
+/// resent on their own, with backoff, according to the
+/// [`retry_policy`](Builder::retry_policy). Resent events keep their ID, so
+/// subscribers can discard duplicates when an earlier attempt succeeded
+/// without the publisher learning about it, for example after a timeout.
+///
+/// # Examples
+///
+/// ```no_run
+/// use salesforce_core::pubsub::context::Context;
+/// use salesforce_core::pubsub::publisher;
+/// use salesforce_pubsub_v1::eventbus::v1::ProducerEvent;
+/// use std::time::Duration;
+///
+/// # async fn example(context: Context, events: Vec<ProducerEvent>) -> Result<(), Box<dyn std::error::Error>> {
+/// let publisher = publisher::Builder::new()
+///     .context(context)
+///     .topic_name("/event/Order__e")
+///     .linger(Duration::from_millis(50))
+///     .build()?;
+///
+/// let mut deliveries = Vec::new();
+/// for event in events {
+///     deliveries.push(publisher.publish(event).await?);
+/// }
+/// for delivery in deliveries {
+///     let replay_id = delivery.await?;
+///     println!("Published with replay ID {replay_id:?}");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Publisher {
+    queue: mpsc::Sender<Queued>,
+    max_bytes: usize,
+}
+
+impl Publisher {
+    /// Queues `event` for publishing.
+    ///
+    /// Waits while the queue is full, then returns a [`Delivery`] for the
+    /// event's replay ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EventTooLarge`] if the event cannot fit in a batch,
+    /// or [`Error::Closed`] if the publisher has stopped.
+    pub async fn publish(&self, mut event: ProducerEvent) -> Result<Delivery, Error> {
+        if event.id.is_empty() {
+            event.id = new_event_id();
+        }
+        let size = event.encoded_len();
+        if size > self.max_bytes {
+            return Err(Error::EventTooLarge {
+                event_id: event.id,
+                size,
+                max_bytes: self.max_bytes,
+            });
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        self.queue
+            .send(Queued {
+                event,
+                size,
+                result: sender,
+            })
+            .await
+            .map_err(|_| Error::Closed)?;
+        Ok(Delivery { result: receiver })
+    }
+}
+
+/// Builder for creating a [`Publisher`].
+#[derive(Debug)]
+pub struct Builder {
+    context: Option<Context>,
+    topic_name: Option<String>,
+    max_events: usize,
+    max_bytes: usize,
+    linger: Duration,
+    retry_policy: RetryPolicy,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            context: None,
+            topic_name: None,
+            max_events: DEFAULT_MAX_EVENTS,
+            max_bytes: DEFAULT_MAX_BYTES,
+            linger: DEFAULT_LINGER,
+            retry_policy: RetryPolicy {
+                max_retries: Some(DEFAULT_MAX_RETRIES),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl Builder {
+    /// Creates a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the context used to publish.
+    pub fn context(mut self, context: Context) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Sets the topic to publish to.
+    pub fn topic_name(mut self, topic_name: impl Into<String>) -> Self {
+        self.topic_name = Some(topic_name.into());
+        self
+    }
+
+    /// Sets the maximum number of events per batch. Defaults to
+    /// [`DEFAULT_MAX_EVENTS`].
+    pub fn max_events(mut self, max_events: usize) -> Self {
+        self.max_events = max_events.max(1);
+        self
+    }
+
+    /// Sets the maximum encoded size of the events in a batch. Defaults to
+    /// [`DEFAULT_MAX_BYTES`].
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Sets how long an incomplete batch waits for more events. Defaults to
+    /// [`DEFAULT_LINGER`].
+    pub fn linger(mut self, linger: Duration) -> Self {
+        self.linger = linger;
+        self
+    }
+
+    /// Sets the backoff and number of retries for failed events. Defaults
+    /// to [`DEFAULT_MAX_RETRIES`] retries with the default backoff of
+    /// [`RetryPolicy`].
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Starts the publisher in a background task.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the context or topic name is missing.
+    pub fn build(self) -> Result<Publisher, Error> {
+        let context = self
+            .context
+            .ok_or_else(|| Error::MissingRequiredAttribute("context".to_string()))?;
+        let topic_name = self
+            .topic_name
+            .ok_or_else(|| Error::MissingRequiredAttribute("topic_name".to_string()))?;
+
+        let (queue, receiver) = mpsc::channel(self.max_events);
+        tokio::spawn(
+            Batcher {
+                context,
+                topic_name,
+                max_events: self.max_events,
+                max_bytes: self.max_bytes,
+                linger: self.linger,
+                retry_policy: self.retry_policy,
+                batch: Vec::new(),
+                batch_bytes: 0,
+            }
+            .run(receiver),
+        );
+        Ok(Publisher {
+            queue,
+            max_bytes: self.max_bytes,
+        })
+    }
+}
+
+/// Background task that groups queued events into Publish requests.
+struct Batcher {
+    context: Context,
+    topic_name: String,
+    max_events: usize,
+    max_bytes: usize,
+    linger: Duration,
+    retry_policy: RetryPolicy,
+    batch: Vec<Queued>,
+    batch_bytes: usize,
+}
+
+impl Batcher {
+    async fn run(mut self, mut receiver: mpsc::Receiver<Queued>) {
+        let mut deadline = Instant::now();
+        loop {
+            tokio::select! {
+                queued = receiver.recv() => {
+                    let Some(queued) = queued else {
+                        break;
+                    };
+                    let duplicate = self
+                        .batch
+                        .iter()
+                        .any(|other| other.event.id == queued.event.id);
+                    if duplicate || self.batch_bytes + queued.size > self.max_bytes {
+                        self.flush().await;
+                    }
+                    if self.batch.is_empty() {
+                        deadline = Instant::now() + self.linger;
+                    }
+                    self.batch_bytes += queued.size;
+                    self.batch.push(queued);
+                    if self.batch.len() >= self.max_events {
+                        self.flush().await;
+                    }
+                }
+                _ = tokio::time::sleep_until(deadline), if !self.batch.is_empty() => {
+                    self.flush().await;
+                }
+            }
+        }
+        self.flush().await;
+    }
+
+    /// Publishes the current batch, resending failed events until they
+    /// succeed, fail permanently or run out of retries.
+    async fn flush(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+        let batch = std::mem::take(&mut self.batch);
+        self.batch_bytes = 0;
+
+        let (mut events, mut senders): (Vec<_>, HashMap<_, _>) = batch
+            .into_iter()
+            .map(|queued| {
+                let sender = (queued.event.id.clone(), queued.result);
+                (queued.event, sender)
+            })
+            .unzip();
+
+        let mut retry = 0;
+        loop {
+            let failures = self.publish(events, &mut senders).await;
+            if failures.is_empty() {
+                return;
+            }
+            retry += 1;
+            if !self.retry_policy.allows(retry) {
+                for (event, error) in failures {
+                    if let Some(sender) = senders.remove(&event.id) {
+                        let _ = sender.send(Err(error));
+                    }
+                }
+                return;
+            }
+            let backoff = self.retry_policy.backoff(retry);
+            tracing::warn!(
+                "Retrying {} events for {} in {backoff:?}: {}",
+                failures.len(),
+                self.topic_name,
+                failures[0].1
+            );
+            tokio::time::sleep(backoff).await;
+            events = failures.into_iter().map(|(event, _)| event).collect();
+        }
+    }
+
+    /// Sends `events` in one Publish request.
+    ///
+    /// Resolves events that succeed or fail permanently, and returns the
+    /// others with their error.
+    async fn publish(
+        &mut self,
+        events: Vec<ProducerEvent>,
+        senders: &mut HashMap<String, oneshot::Sender<Result<Vec<u8>, Error>>>,
+    ) -> Vec<(ProducerEvent, Error)> {
+        tracing::debug!("Publishing {} events to {}", events.len(), self.topic_name);
+        let response = self
+            .context
+            .publish(PublishRequest {
+                topic_name: self.topic_name.clone(),
+                events: events.clone(),
+                ..Default::default()
+            })
+            .await;
+
+        let mut results: HashMap<_, _> = match response {
+            Ok(response) => response
+                .into_inner()
+                .results
+                .into_iter()
+                .map(|result| (result.correlation_key.clone(), event_result(result)))
+                .collect(),
+            Err(e) => {
+                if let Some(GrpcError::AuthExpired(_)) = e.grpc_error() {
+                    if let Err(e) = self.context.token_provider().refresh().await {
+                        tracing::warn!("Failed to refresh access token: {e}");
+                    }
+                }
+                let source = Arc::new(e);
+                events
+                    .iter()
+                    .map(|event| {
+                        let error = Error::Publish {
+                            topic_name: self.topic_name.clone(),
+                            source: source.clone(),
+                        };
+                        (event.id.clone(), Err(error))
+                    })
+                    .collect()
+            }
+        };
+
+        let mut failures = Vec::new();
+        for event in events {
+            let result = results.remove(&event.id).unwrap_or_else(|| {
+                Err(Error::MissingResult {
+                    event_id: event.id.clone(),
+                })
+            });
+            match result {
+                Err(error) if error.is_retryable() => failures.push((event, error)),
+                result => {
+                    if let Some(sender) = senders.remove(&event.id) {
+                        let _ = sender.send(result);
+                    }
+                }
+            }
+        }
+        failures
+    }
+}
+
+/// Converts the server's result for one event.
+fn event_result(result: PublishResult) -> Result<Vec<u8>, Error> {
+    match result.error {
+        Some(error) => Err(Error::Rejected {
+            event_id: result.correlation_key,
+            code: ErrorCode::try_from(error.code).unwrap_or(ErrorCode::Unknown),
+            message: error.msg,
+        }),
+        None => Ok(result.replay_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::testing::{self, FakePubSub};
+
+    const TOPIC: &str = "/event/Order__e";
+
+    fn event(id: &str, payload_len: usize) -> ProducerEvent {
+        ProducerEvent {
+            id: id.to_string(),
+            schema_id: "schema-1".to_string(),
+            payload: vec![0; payload_len],
+            ..Default::default()
+        }
+    }
+
+    fn fast_retries(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            max_retries: Some(max_retries),
+            ..Default::default()
+        }
+    }
+
+    async fn publisher(fake: &Arc<FakePubSub>, builder: Builder) -> Publisher {
+        let context = testing::serve(fake.clone()).await;
+        builder.context(context).topic_name(TOPIC).build().unwrap()
+    }
+
+    fn batch_sizes(fake: &FakePubSub) -> Vec<usize> {
+        fake.published
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| request.events.len())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_batches_by_count_and_linger() {
+        let fake = Arc::new(FakePubSub::default());
+        let builder = Builder::new()
+            .max_events(2)
+            .linger(Duration::from_millis(20));
+        let publisher = publisher(&fake, builder).await;
+
+        let mut deliveries = Vec::new();
+        for id in ["a", "b", "c"] {
+            deliveries.push(publisher.publish(event(id, 10)).await.unwrap());
+        }
+        let mut replay_ids = Vec::new();
+        for delivery in deliveries {
+            replay_ids.push(delivery.await.unwrap());
+        }
+
+        assert_eq!(batch_sizes(&fake), vec![2, 1]);
+        let expected: Vec<Vec<u8>> = (0u64..3).map(|i| i.to_be_bytes().to_vec()).collect();
+        assert_eq!(replay_ids, expected);
+        assert_eq!(fake.published.lock().unwrap()[0].topic_name, TOPIC);
+    }
+
+    #[tokio::test]
+    async fn test_batches_by_bytes() {
+        let fake = Arc::new(FakePubSub::default());
+        let size = event("a", 100).encoded_len();
+        let builder = Builder::new()
+            .max_bytes(size * 2)
+            .linger(Duration::from_millis(5));
+        let publisher = publisher(&fake, builder).await;
+
+        let mut deliveries = Vec::new();
+        for id in ["a", "b", "c"] {
+            deliveries.push(publisher.publish(event(id, 100)).await.unwrap());
+        }
+        for delivery in deliveries {
+            delivery.await.unwrap();
+        }
+        assert_eq!(batch_sizes(&fake), vec![2, 1]);
+
+        let error = publisher.publish(event("big", 1000)).await.unwrap_err();
+        assert!(matches!(error, Error::EventTooLarge { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_event_gets_typed_error() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.rejected_events.lock().unwrap().push("bad".to_string());
+        let builder = Builder::new().retry_policy(fast_retries(0));
+        let publisher = publisher(&fake, builder).await;
+
+        let good = publisher.publish(event("good", 1)).await.unwrap();
+        let bad = publisher.publish(event("bad", 1)).await.unwrap();
+        assert!(good.await.is_ok());
+        assert!(matches!(
+            bad.await,
+            Err(Error::Rejected { event_id, code: ErrorCode::Publish, .. }) if event_id == "bad"
+        ));
+        assert_eq!(batch_sizes(&fake), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_assigns_ids_and_flushes_on_drop() {
+        let fake = Arc::new(FakePubSub::default());
+        let builder = Builder::new().linger(Duration::from_secs(60));
+        let publisher = publisher(&fake, builder).await;
+
+        let first = publisher.publish(event("", 1)).await.unwrap();
+        let second = publisher.publish(event("", 1)).await.unwrap();
+        drop(publisher);
+        assert!(first.await.is_ok());
+        assert!(second.await.is_ok());
+
+        let published = fake.published.lock().unwrap();
+        let ids: Vec<_> = published[0].events.iter().map(|e| e.id.clone()).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.iter().all(|id| id.len() == 36));
+        assert_ne!(ids[0], ids[1]);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_ids_split_batches() {
+        let fake = Arc::new(FakePubSub::default());
+        let publisher = publisher(&fake, Builder::new()).await;
+
+        let first = publisher.publish(event("same", 1)).await.unwrap();
+        let second = publisher.publish(event("same", 1)).await.unwrap();
+        assert!(first.await.is_ok());
+        assert!(second.await.is_ok());
+        assert_eq!(batch_sizes(&fake), vec![1, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_retries_only_failed_events_with_same_id() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.transient_rejections
+            .lock()
+            .unwrap()
+            .insert("b".to_string(), 1);
+        let builder = Builder::new().retry_policy(fast_retries(3));
+        let publisher = publisher(&fake, builder).await;
+
+        let mut deliveries = Vec::new();
+        for id in ["a", "b", "c"] {
+            deliveries.push(publisher.publish(event(id, 1)).await.unwrap());
+        }
+        for delivery in deliveries {
+            delivery.await.unwrap();
+        }
+
+        let published = fake.published.lock().unwrap();
+        assert_eq!(published.len(), 2);
+        assert_eq!(published[0].events.len(), 3);
+        assert_eq!(published[1].events.len(), 1);
+        assert_eq!(published[1].events[0].id, "b");
+        assert_eq!(published[1].events[0], published[0].events[1]);
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_status() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.publish_failures
+            .lock()
+            .unwrap()
+            .push_back(tonic::Status::unavailable("try again"));
+        let builder = Builder::new().retry_policy(fast_retries(3));
+        let publisher = publisher(&fake, builder).await;
+
+        let delivery = publisher.publish(event("a", 1)).await.unwrap();
+        assert!(delivery.await.is_ok());
+        assert_eq!(batch_sizes(&fake), vec![1]);
+        assert!(fake.publish_failures.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_permanent_status_is_not_retried() {
+        let fake = Arc::new(FakePubSub::default());
+        for _ in 0..2 {
+            fake.publish_failures
+                .lock()
+                .unwrap()
+                .push_back(tonic::Status::permission_denied("no access"));
+        }
+        let builder = Builder::new().retry_policy(fast_retries(3));
+        let publisher = publisher(&fake, builder).await;
+
+        let error = publisher
+            .publish(event("a", 1))
+            .await
+            .unwrap()
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Publish { .. }));
+        assert!(!error.is_retryable());
+        assert_eq!(fake.publish_failures.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.rejected_events.lock().unwrap().push("bad".to_string());
+        let builder = Builder::new().retry_policy(fast_retries(2));
+        let publisher = publisher(&fake, builder).await;
+
+        let error = publisher
+            .publish(event("bad", 1))
+            .await
+            .unwrap()
+            .await
+            .unwrap_err();
+        assert!(error.is_retryable());
+        assert!(matches!(error, Error::Rejected { .. }));
+        assert_eq!(batch_sizes(&fake), vec![1, 1, 1]);
+    }
+}
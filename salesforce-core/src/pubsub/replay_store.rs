@@ -0,0 +1,252 @@
+use crate::atomic_file;
+use base64::Engine as _;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Errors that can occur while reading or writing replay IDs.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Failed to read the replay store file from disk.
+    #[error("Failed to read replay store at {path}: {source}")]
+    Read {
+        /// Path to the replay store file.
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// Failed to write the replay store file to disk.
+    #[error("Failed to write replay store at {path}: {source}")]
+    Write {
+        /// Path to the replay store file.
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The replay store file is not valid JSON.
+    #[error("Failed to parse replay store at {path}: {source}")]
+    Parse {
+        /// Path to the replay store file.
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// A stored replay ID is not valid base64.
+    #[error("Invalid replay ID for {topic_name} in replay store: {source}")]
+    InvalidReplayId {
+        /// Name of the topic.
+        topic_name: String,
+        #[source]
+        source: base64::DecodeError,
+    },
+}
+
+/// Persists the replay ID of the last processed event per topic.
+///
+/// A [`Subscription`](crate::pubsub::subscriber::Subscription) with a store
+/// starts after the stored replay ID, and saves the replay IDs of events the
+/// application marks as processed. Events processed but not yet saved are
+/// delivered again after a restart, which gives at-least-once delivery.
+///
+/// Implementations may block: the subscription loads and saves from Tokio's
+/// blocking thread pool.
+pub trait ReplayStore: std::fmt::Debug + Send + Sync {
+    /// Returns the replay ID stored for `topic_name`, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backing storage cannot be read.
+    fn load(&self, topic_name: &str) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Saves `replay_id` for `topic_name`, replacing any previous value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backing storage cannot be written.
+    fn save(&self, topic_name: &str, replay_id: &[u8]) -> Result<(), Error>;
+}
+
+/// Replay store that keeps replay IDs in memory.
+///
+/// Useful for tests and for resuming subscriptions within one process.
+#[derive(Debug, Default)]
+pub struct MemoryReplayStore {
+    replay_ids: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryReplayStore {
+    /// Creates an empty in-memory replay store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ReplayStore for MemoryReplayStore {
+    fn load(&self, topic_name: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.replay_ids.lock().unwrap().get(topic_name).cloned())
+    }
+
+    fn save(&self, topic_name: &str, replay_id: &[u8]) -> Result<(), Error> {
+        self.replay_ids
+            .lock()
+            .unwrap()
+            .insert(topic_name.to_string(), replay_id.to_vec());
+        Ok(())
+    }
+}
+
+/// Replay store that keeps replay IDs in a JSON file.
+///
+/// The file maps topic names to base64-encoded replay IDs. It is replaced
+/// atomically on every save, so a crash never leaves a partially written
+/// checkpoint behind.
+///
+/// # Examples
+///
+/// ```
+/// use salesforce_core::pubsub::replay_store::{FileReplayStore, ReplayStore};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let dir = tempfile::tempdir()?;
+/// # let path = dir.path().join("replay.json");
+/// let store = FileReplayStore::new(path);
+/// store.save("/event/Order__e", &[0, 0, 0, 42])?;
+/// assert_eq!(store.load("/event/Order__e")?, Some(vec![0, 0, 0, 42]));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FileReplayStore {
+    path: PathBuf,
+    /// Serializes read-modify-write cycles within the process.
+    lock: Mutex<()>,
+}
+
+impl FileReplayStore {
+    /// Creates a store backed by the file at `path`.
+    ///
+    /// The file is created on the first save.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Returns the path of the backing file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads all entries, treating a missing file as empty.
+    fn read_entries(&self) -> Result<BTreeMap<String, String>, Error> {
+        let contents = match fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => {
+                return Err(Error::Read {
+                    path: self.path.clone(),
+                    source: e,
+                });
+            }
+        };
+        serde_json::from_slice(&contents).map_err(|e| Error::Parse {
+            path: self.path.clone(),
+            source: e,
+        })
+    }
+
+    /// Atomically writes all entries.
+    fn write_entries(&self, entries: &BTreeMap<String, String>) -> Result<(), Error> {
+        let write_error = |e| Error::Write {
+            path: self.path.clone(),
+            source: e,
+        };
+        let contents = serde_json::to_vec_pretty(entries).map_err(|e| write_error(e.into()))?;
+        atomic_file::write(&self.path, &contents).map_err(write_error)
+    }
+}
+
+impl ReplayStore for FileReplayStore {
+    fn load(&self, topic_name: &str) -> Result<Option<Vec<u8>>, Error> {
+        let _guard = self.lock.lock().unwrap();
+        self.read_entries()?
+            .get(topic_name)
+            .map(|encoded| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(encoded)
+                    .map_err(|e| Error::InvalidReplayId {
+                        topic_name: topic_name.to_string(),
+                        source: e,
+                    })
+            })
+            .transpose()
+    }
+
+    fn save(&self, topic_name: &str, replay_id: &[u8]) -> Result<(), Error> {
+        let _guard = self.lock.lock().unwrap();
+        let mut entries = self.read_entries()?;
+        entries.insert(
+            topic_name.to_string(),
+            base64::engine::general_purpose::STANDARD.encode(replay_id),
+        );
+        self.write_entries(&entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_store_round_trip() {
+        let store = MemoryReplayStore::new();
+        assert_eq!(store.load("/event/A__e").unwrap(), None);
+        store.save("/event/A__e", &[1]).unwrap();
+        store.save("/event/A__e", &[2]).unwrap();
+        assert_eq!(store.load("/event/A__e").unwrap(), Some(vec![2]));
+        assert_eq!(store.load("/event/B__e").unwrap(), None);
+    }
+
+    #[test]
+    fn test_file_store_keeps_topics_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replay.json");
+        let store = FileReplayStore::new(path.clone());
+        assert_eq!(store.load("/event/A__e").unwrap(), None);
+
+        store.save("/event/A__e", &[0, 1]).unwrap();
+        store.save("/event/B__e", &[0, 2]).unwrap();
+        store.save("/event/A__e", &[0, 3]).unwrap();
+
+        let reopened = FileReplayStore::new(path);
+        assert_eq!(reopened.load("/event/A__e").unwrap(), Some(vec![0, 3]));
+        assert_eq!(reopened.load("/event/B__e").unwrap(), Some(vec![0, 2]));
+        let leftovers: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name != "replay.json")
+            .collect();
+        assert!(leftovers.is_empty(), "{leftovers:?}");
+    }
+
+    #[test]
+    fn test_file_store_rejects_corrupt_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replay.json");
+        fs::write(&path, "not json").unwrap();
+        let store = FileReplayStore::new(path.clone());
+        assert!(matches!(
+            store.load("/event/A__e"),
+            Err(Error::Parse { .. })
+        ));
+
+        fs::write(&path, r#"{"/event/A__e": "%%%"}"#).unwrap();
+        assert!(matches!(
+            store.load("/event/A__e"),
+            Err(Error::InvalidReplayId { .. })
+        ));
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+/// Exponential backoff with an optional cap on retries.
+///
+/// The delay before retry `n` (starting at 1) is
+/// `initial_backoff * multiplier^(n - 1)`, capped at `max_backoff`.
+///
+/// # Examples
+///
+/// ```
+/// use salesforce_core::pubsub::retry::RetryPolicy;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy {
+///     initial_backoff: Duration::from_millis(100),
+///     max_backoff: Duration::from_secs(1),
+///     max_retries: Some(5),
+///     ..Default::default()
+/// };
+/// assert_eq!(policy.backoff(1), Duration::from_millis(100));
+/// assert_eq!(policy.backoff(3), Duration::from_millis(400));
+/// assert_eq!(policy.backoff(10), Duration::from_secs(1));
+/// assert!(policy.allows(5));
+/// assert!(!policy.allows(6));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Delay before the first retry. Defaults to 500 milliseconds.
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay. Defaults to 30 seconds.
+    pub max_backoff: Duration,
+    /// Factor the delay grows by with each retry. Defaults to 2.
+    pub multiplier: f64,
+    /// Maximum number of retries after the first attempt, or `None` to retry
+    /// forever. Defaults to `None`.
+    pub max_retries: Option<u32>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            max_retries: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before retry `retry`, counting from 1.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        Duration::try_from_secs_f64(delay)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+
+    /// Returns whether retry `retry`, counting from 1, is allowed.
+    pub fn allows(&self, retry: u32) -> bool {
+        self.max_retries
+            .is_none_or(|max_retries| retry <= max_retries)
+    }
+}
@@ -0,0 +1,525 @@
+use crate::atomic_file;
+use crate::pubsub::avro;
+use crate::pubsub::context::{self, Context};
+use salesforce_pubsub_v1::eventbus::v1::{ProducerEvent, SchemaRequest};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Errors that can occur while looking up schemas or decoding events.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The GetSchema call failed.
+    #[error("Failed to fetch schema {schema_id}: {source}")]
+    GetSchema {
+        /// ID of the schema that was requested.
+        schema_id: String,
+        #[source]
+        source: context::Error,
+    },
+    /// The schema returned by the server is not a valid Avro schema.
+    #[error("Failed to parse schema {schema_id}: {source}")]
+    ParseSchema {
+        /// ID of the schema that failed to parse.
+        schema_id: String,
+        #[source]
+        source: avro::Error,
+    },
+    /// The event payload does not match its schema.
+    #[error("Failed to decode event {event_id} with schema {schema_id}: {source}")]
+    Decode {
+        /// ID of the event that failed to decode.
+        event_id: String,
+        /// ID of the schema the event was written with.
+        schema_id: String,
+        #[source]
+        source: avro::Error,
+    },
+}
+
+/// Schema fetched from the Pub/Sub API.
+#[derive(Debug)]
+pub struct CachedSchema {
+    /// Schema ID assigned by Salesforce.
+    pub schema_id: String,
+    /// Schema JSON as returned by GetSchema.
+    pub schema_json: String,
+    /// CRC-64-AVRO fingerprint of the schema's canonical form.
+    pub fingerprint: u64,
+    /// Parsed schema, shared by all schema IDs with the same fingerprint.
+    pub schema: Arc<avro::Schema>,
+}
+
+/// Cache of topic schemas keyed by schema ID.
+///
+/// Each schema is fetched with GetSchema at most once, even when several
+/// tasks ask for it at the same time. Parsed schemas are shared by
+/// fingerprint, so schema IDs that describe the same schema reuse one
+/// [`avro::Schema`]. With a [snapshot](Self::snapshot) the cache survives
+/// restarts.
+///
+/// Cloning a cache is cheap: clones share their entries.
+///
+/// # Examples
+///
+/// ```no_run
+/// use salesforce_core::pubsub::context::Context;
+/// use salesforce_core::pubsub::schema_cache::SchemaCache;
+/// use salesforce_pubsub_v1::eventbus::v1::FetchResponse;
+///
+/// # async fn example(context: Context, response: FetchResponse) -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = SchemaCache::new(context).snapshot("schemas.json");
+/// let mut decoder = cache.decoder();
+///
+/// for event in response.events.iter().filter_map(|e| e.event.as_ref()) {
+///     let decoded = decoder.decode(event).await?;
+///     if decoded.schema_changed {
+///         println!("Topic schema changed to {}", decoded.schema.schema_id);
+///     }
+///     println!("{:?}", decoded.value);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SchemaCache {
+    inner: Arc<Inner>,
+    snapshot_path: Option<Arc<PathBuf>>,
+}
+
+struct Inner {
+    context: Context,
+    entries: Mutex<HashMap<String, Arc<OnceCell<Arc<CachedSchema>>>>>,
+    by_fingerprint: Mutex<HashMap<u64, Arc<avro::Schema>>>,
+    /// Serializes snapshot writes so concurrent fetches don't race on the file.
+    snapshot_lock: tokio::sync::Mutex<()>,
+}
+
+impl std::fmt::Debug for SchemaCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SchemaCache")
+            .field("schema_ids", &self.schema_ids())
+            .field("snapshot_path", &self.snapshot_path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SchemaCache {
+    /// Creates an empty cache that fetches schemas through `context`.
+    pub fn new(context: Context) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                context,
+                entries: Mutex::new(HashMap::new()),
+                by_fingerprint: Mutex::new(HashMap::new()),
+                snapshot_lock: tokio::sync::Mutex::new(()),
+            }),
+            snapshot_path: None,
+        }
+    }
+
+    /// Persists fetched schemas to `path` and preloads the schemas already
+    /// stored there.
+    ///
+    /// A missing or unreadable snapshot is logged and treated as empty.
+    pub fn snapshot(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        for (schema_id, schema_json) in read_snapshot(&path) {
+            if let Err(e) = self.insert(&schema_id, schema_json) {
+                tracing::warn!("Skipping schema {schema_id} from snapshot: {e}");
+            }
+        }
+        self.snapshot_path = Some(Arc::new(path));
+        self
+    }
+
+    /// Returns the context used to fetch schemas.
+    pub fn context(&self) -> &Context {
+        &self.inner.context
+    }
+
+    /// Returns the IDs of all cached schemas.
+    pub fn schema_ids(&self) -> Vec<String> {
+        let entries = self.inner.entries.lock().unwrap();
+        let mut ids: Vec<_> = entries
+            .iter()
+            .filter(|(_, cell)| cell.initialized())
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Adds a schema without fetching it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ParseSchema`] if the schema is not valid.
+    pub fn insert(
+        &self,
+        schema_id: &str,
+        schema_json: impl Into<String>,
+    ) -> Result<Arc<CachedSchema>, Error> {
+        let cached = Arc::new(self.parse(schema_id, schema_json.into())?);
+        let cell = OnceCell::new_with(Some(cached.clone()));
+        self.inner
+            .entries
+            .lock()
+            .unwrap()
+            .insert(schema_id.to_string(), Arc::new(cell));
+        Ok(cached)
+    }
+
+    /// Returns the schema with the given ID, fetching it on first use.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::GetSchema`] if the schema cannot be fetched, or
+    /// [`Error::ParseSchema`] if it is not valid. Failures are not cached.
+    pub async fn get(&self, schema_id: &str) -> Result<Arc<CachedSchema>, Error> {
+        let cell = self
+            .inner
+            .entries
+            .lock()
+            .unwrap()
+            .entry(schema_id.to_string())
+            .or_default()
+            .clone();
+        let mut fetched = false;
+        let cached = cell
+            .get_or_try_init(|| {
+                fetched = true;
+                self.fetch(schema_id)
+            })
+            .await?
+            .clone();
+        if fetched {
+            self.write_snapshot().await;
+        }
+        Ok(cached)
+    }
+
+    /// Decodes an event payload with the schema it was written with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the schema cannot be looked up or the payload does
+    /// not match it.
+    pub async fn decode(&self, event: &ProducerEvent) -> Result<avro::Value, Error> {
+        let schema = self.get(&event.schema_id).await?;
+        decode_with(&schema, event)
+    }
+
+    /// Returns a decoder that reports when the schema of a stream changes.
+    pub fn decoder(&self) -> EventDecoder {
+        EventDecoder {
+            cache: self.clone(),
+            current: None,
+        }
+    }
+
+    async fn fetch(&self, schema_id: &str) -> Result<Arc<CachedSchema>, Error> {
+        let schema_info = self
+            .inner
+            .context
+            .clone()
+            .get_schema(SchemaRequest {
+                schema_id: schema_id.to_string(),
+            })
+            .await
+            .map_err(|e| Error::GetSchema {
+                schema_id: schema_id.to_string(),
+                source: e,
+            })?
+            .into_inner();
+        tracing::debug!("Fetched schema {schema_id}");
+
+        Ok(Arc::new(self.parse(schema_id, schema_info.schema_json)?))
+    }
+
+    /// Parses a schema, reusing the parsed schema of an equal fingerprint.
+    fn parse(&self, schema_id: &str, schema_json: String) -> Result<CachedSchema, Error> {
+        let schema = avro::Schema::parse(&schema_json).map_err(|e| Error::ParseSchema {
+            schema_id: schema_id.to_string(),
+            source: e,
+        })?;
+        let fingerprint = schema.fingerprint();
+        let schema = self
+            .inner
+            .by_fingerprint
+            .lock()
+            .unwrap()
+            .entry(fingerprint)
+            .or_insert_with(|| Arc::new(schema))
+            .clone();
+        Ok(CachedSchema {
+            schema_id: schema_id.to_string(),
+            schema_json,
+            fingerprint,
+            schema,
+        })
+    }
+
+    /// Writes all cached schemas to the snapshot file, if one is configured.
+    ///
+    /// The file is written on Tokio's blocking thread pool.
+    async fn write_snapshot(&self) {
+        let Some(path) = &self.snapshot_path else {
+            return;
+        };
+        let _guard = self.inner.snapshot_lock.lock().await;
+        let schemas: BTreeMap<String, String> = self
+            .inner
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|cell| cell.get())
+            .map(|cached| (cached.schema_id.clone(), cached.schema_json.clone()))
+            .collect();
+        let snapshot_path = path.clone();
+        let written =
+            tokio::task::spawn_blocking(move || write_snapshot_file(&snapshot_path, &schemas))
+                .await
+                .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+        if let Err(e) = written {
+            tracing::warn!("Failed to write schema snapshot {}: {e}", path.display());
+        }
+    }
+}
+
+/// Event decoded by an [`EventDecoder`].
+#[derive(Debug, Clone)]
+pub struct DecodedEvent {
+    /// Decoded payload.
+    pub value: avro::Value,
+    /// Schema the event was written with.
+    pub schema: Arc<CachedSchema>,
+    /// Whether the schema differs from that of the previous event.
+    pub schema_changed: bool,
+}
+
+/// Decodes the events of one stream and notices schema changes.
+///
+/// Events of a topic normally share a schema. When a field is added to the
+/// topic, later events carry a new schema ID; the decoder fetches it and
+/// flags the first event with the new schema.
+#[derive(Debug, Clone)]
+pub struct EventDecoder {
+    cache: SchemaCache,
+    current: Option<Arc<CachedSchema>>,
+}
+
+impl EventDecoder {
+    /// Returns the schema of the last decoded event.
+    pub fn current_schema(&self) -> Option<&Arc<CachedSchema>> {
+        self.current.as_ref()
+    }
+
+    /// Decodes an event payload with the schema it was written with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the schema cannot be looked up or the payload does
+    /// not match it.
+    pub async fn decode(&mut self, event: &ProducerEvent) -> Result<DecodedEvent, Error> {
+        let schema = match &self.current {
+            Some(current) if current.schema_id == event.schema_id => current.clone(),
+            _ => self.cache.get(&event.schema_id).await?,
+        };
+        let value = decode_with(&schema, event)?;
+
+        let schema_changed = self
+            .current
+            .as_ref()
+            .is_some_and(|current| current.fingerprint != schema.fingerprint);
+        if schema_changed {
+            tracing::info!(
+                "Schema changed from {} to {}",
+                self.current.as_ref().map_or("", |c| c.schema_id.as_str()),
+                schema.schema_id
+            );
+        }
+        self.current = Some(schema.clone());
+
+        Ok(DecodedEvent {
+            value,
+            schema,
+            schema_changed,
+        })
+    }
+}
+
+fn decode_with(schema: &CachedSchema, event: &ProducerEvent) -> Result<avro::Value, Error> {
+    schema
+        .schema
+        .decode(&event.payload)
+        .map_err(|e| Error::Decode {
+            event_id: event.id.clone(),
+            schema_id: event.schema_id.clone(),
+            source: e,
+        })
+}
+
+fn read_snapshot(path: &Path) -> BTreeMap<String, String> {
+    let contents = match std::fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return BTreeMap::new(),
+        Err(e) => {
+            tracing::warn!("Failed to read schema snapshot {}: {e}", path.display());
+            return BTreeMap::new();
+        }
+    };
+    serde_json::from_slice(&contents).unwrap_or_else(|e| {
+        tracing::warn!("Ignoring invalid schema snapshot {}: {e}", path.display());
+        BTreeMap::new()
+    })
+}
+
+/// Atomically replaces the snapshot at `path`.
+fn write_snapshot_file(path: &Path, schemas: &BTreeMap<String, String>) -> std::io::Result<()> {
+    let contents = serde_json::to_vec(schemas)?;
+    atomic_file::write(path, &contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::testing::{self, FakePubSub};
+    use std::sync::atomic::Ordering;
+
+    const SCHEMA_V1: &str = r#"{"type": "record", "name": "Order_Placed__e", "fields": [
+        {"name": "Order_Number__c", "type": ["null", "string"], "default": null}
+    ]}"#;
+
+    const SCHEMA_V2: &str = r#"{"type": "record", "name": "Order_Placed__e", "fields": [
+        {"name": "Order_Number__c", "type": ["null", "string"], "default": null},
+        {"name": "Amount__c", "type": ["null", "double"], "default": null}
+    ]}"#;
+
+    fn event(schema_id: &str, payload: &[u8]) -> ProducerEvent {
+        ProducerEvent {
+            id: "event-1".to_string(),
+            schema_id: schema_id.to_string(),
+            payload: payload.to_vec(),
+            headers: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_fetches_once() {
+        let fake = Arc::new(FakePubSub::default().with_schema("v1", SCHEMA_V1));
+        let cache = SchemaCache::new(testing::serve(fake.clone()).await);
+
+        let (first, second) = tokio::join!(cache.get("v1"), cache.get("v1"));
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert!(Arc::ptr_eq(&first, &second));
+        cache.get("v1").await.unwrap();
+        assert_eq!(fake.get_schema_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.schema_ids(), vec!["v1"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_failure_is_not_cached() {
+        let fake = Arc::new(FakePubSub::default());
+        let cache = SchemaCache::new(testing::serve(fake.clone()).await);
+
+        assert!(matches!(
+            cache.get("v1").await,
+            Err(Error::GetSchema { schema_id, .. }) if schema_id == "v1"
+        ));
+        fake.schemas
+            .lock()
+            .unwrap()
+            .insert("v1".to_string(), SCHEMA_V1.to_string());
+        cache.get("v1").await.unwrap();
+        assert_eq!(fake.get_schema_calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_same_fingerprint_shares_schema() {
+        let fake = Arc::new(FakePubSub::default());
+        let cache = SchemaCache::new(testing::serve(fake).await);
+
+        let first = cache.insert("a", SCHEMA_V1).unwrap();
+        let reformatted = cache
+            .insert(
+                "b",
+                serde_json::to_string(
+                    &serde_json::from_str::<serde_json::Value>(SCHEMA_V1).unwrap(),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        assert_eq!(first.fingerprint, reformatted.fingerprint);
+        assert!(Arc::ptr_eq(&first.schema, &reformatted.schema));
+        assert!(matches!(
+            cache.insert("c", "{}"),
+            Err(Error::ParseSchema { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_decoder_detects_schema_change() {
+        let fake = Arc::new(
+            FakePubSub::default()
+                .with_schema("v1", SCHEMA_V1)
+                .with_schema("v1-copy", SCHEMA_V1)
+                .with_schema("v2", SCHEMA_V2),
+        );
+        let cache = SchemaCache::new(testing::serve(fake.clone()).await);
+        let mut decoder = cache.decoder();
+
+        let first = decoder.decode(&event("v1", b"\x02\x02A")).await.unwrap();
+        assert!(!first.schema_changed);
+        assert_eq!(
+            first.value.field("Order_Number__c"),
+            Some(&avro::Value::String("A".to_string()))
+        );
+
+        let same = decoder.decode(&event("v1-copy", b"\x00")).await.unwrap();
+        assert!(!same.schema_changed);
+
+        let changed = decoder.decode(&event("v2", b"\x00\x00")).await.unwrap();
+        assert!(changed.schema_changed);
+        assert_eq!(decoder.current_schema().unwrap().schema_id, "v2");
+
+        decoder.decode(&event("v2", b"\x00\x00")).await.unwrap();
+        assert_eq!(fake.get_schema_calls.load(Ordering::SeqCst), 3);
+
+        assert!(matches!(
+            decoder.decode(&event("v2", b"\x00")).await,
+            Err(Error::Decode { schema_id, .. }) if schema_id == "v2"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schemas.json");
+        let fake = Arc::new(FakePubSub::default().with_schema("v1", SCHEMA_V1));
+        let context = testing::serve(fake.clone()).await;
+
+        let cache = SchemaCache::new(context.clone()).snapshot(&path);
+        cache.get("v1").await.unwrap();
+        assert!(path.exists());
+
+        let restored = SchemaCache::new(context).snapshot(&path);
+        assert_eq!(restored.schema_ids(), vec!["v1"]);
+        restored.get("v1").await.unwrap();
+        assert_eq!(fake.get_schema_calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_snapshot_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schemas.json");
+        std::fs::write(&path, "not json").unwrap();
+        let fake = Arc::new(FakePubSub::default());
+
+        let cache = SchemaCache::new(testing::serve(fake).await).snapshot(&path);
+        assert!(cache.schema_ids().is_empty());
+    }
+}
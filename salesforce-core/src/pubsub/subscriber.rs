@@ -0,0 +1,910 @@
+use crate::pubsub::context::{self, Context};
+use crate::pubsub::error::GrpcError;
+use crate::pubsub::replay_store::{self, ReplayStore};
+use crate::pubsub::retry::RetryPolicy;
+use futures_util::Stream;
+use salesforce_pubsub_v1::eventbus::v1::{ConsumerEvent, FetchRequest, ReplayPreset};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Errors that end a [`Subscription`].
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Required builder attribute is missing.
+    #[error("Missing required attribute: {}", _0)]
+    MissingRequiredAttribute(String),
+    /// The subscription failed with an error that is not worth retrying.
+    #[error("Subscription to {topic_name} failed: {source}")]
+    Subscribe {
+        /// Name of the topic.
+        topic_name: String,
+        #[source]
+        source: context::Error,
+    },
+    /// The subscription kept failing until the retry policy gave up.
+    #[error("Subscription to {topic_name} failed after {retries} retries: {source}")]
+    RetriesExhausted {
+        /// Name of the topic.
+        topic_name: String,
+        /// Number of retries made.
+        retries: u32,
+        #[source]
+        source: context::Error,
+    },
+    /// Loading or saving the replay ID checkpoint failed.
+    #[error("Replay store failed for {topic_name}: {source}")]
+    ReplayStore {
+        /// Name of the topic.
+        topic_name: String,
+        #[source]
+        source: replay_store::Error,
+    },
+}
+
+/// How to recover from a failed Subscribe stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Recovery {
+    /// Resubscribe from the last known replay ID.
+    Resume,
+    /// The replay ID was rejected; resubscribe from the fallback preset.
+    Fallback,
+    /// The access token was rejected; refresh it and resubscribe.
+    Reauthenticate,
+    /// Give up and report the error.
+    Fail,
+}
+
+impl Recovery {
+    fn for_error(error: &context::Error) -> Self {
+        match error {
+            context::Error::Tonic(status) => Recovery::of(status),
+            // Refreshing the token before subscribing fails on network
+            // errors too, which are retried like a dropped stream. A revoked
+            // session or rejected refresh token is permanent.
+            context::Error::TokenRefresh { source } if source.is_retryable() => Recovery::Resume,
+            context::Error::StreamClosed() => Recovery::Resume,
+            _ => Recovery::Fail,
+        }
+    }
+
+    fn of(status: &tonic::Status) -> Self {
+        match GrpcError::from(status) {
+            // Typically the replay ID is older than the retention window.
+            GrpcError::InvalidReplayId(_) => Recovery::Fallback,
+            GrpcError::AuthExpired(_) => Recovery::Reauthenticate,
+            error if error.is_retryable() => Recovery::Resume,
+            _ => Recovery::Fail,
+        }
+    }
+}
+
+/// A Subscribe stream that survives disconnects.
+///
+/// The subscription remembers the replay ID of the last event it received,
+/// or the `latest_replay_id` from keepalive responses, and resubscribes from
+/// there with [`ReplayPreset::Custom`] when the server drops the stream or
+/// fails with a retryable status, or when refreshing the access token
+/// before resubscribing fails. Retries back off according to the
+/// [`RetryPolicy`]. If the server rejects the stored replay ID, the
+/// subscription restarts from the fallback preset instead.
+///
+/// Events are delivered as one continuous stream. The stream ends after
+/// yielding an [`Error`] when the subscription cannot be recovered.
+/// Dropping the subscription closes the underlying gRPC stream.
+///
+/// With a [`ReplayStore`], the subscription starts after the stored replay
+/// ID and saves the replay IDs passed to
+/// [`mark_processed`](Subscription::mark_processed). Saves are batched per
+/// [`Builder::commit_every`] and [`Builder::commit_interval`]; pending
+/// replay IDs are also saved on [`commit`](Subscription::commit) and when
+/// the subscription is dropped. Saves run on Tokio's blocking thread pool,
+/// except the one on drop, which cannot be awaited.
+///
+/// # Examples
+///
+/// ```no_run
+/// use futures_util::StreamExt;
+/// use salesforce_core::pubsub::context::Context;
+/// use salesforce_core::pubsub::replay_store::FileReplayStore;
+/// use salesforce_core::pubsub::subscriber;
+/// use salesforce_pubsub_v1::eventbus::v1::ReplayPreset;
+/// use std::path::PathBuf;
+/// use std::sync::Arc;
+///
+/// # async fn example(context: Context) -> Result<(), Box<dyn std::error::Error>> {
+/// let mut subscription = subscriber::Builder::new()
+///     .context(context)
+///     .topic_name("/data/AccountChangeEvent")
+///     .replay_preset(ReplayPreset::Earliest)
+///     .replay_store(Arc::new(FileReplayStore::new(PathBuf::from("replay.json"))))
+///     .commit_every(50)
+///     .build()?;
+///
+/// while let Some(event) = subscription.next().await {
+///     let event = event?;
+///     println!("Received event {:?}", event.replay_id);
+///     subscription.mark_processed(&event.replay_id).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Subscription {
+    events: mpsc::Receiver<Result<ConsumerEvent, Error>>,
+    task: tokio::task::JoinHandle<()>,
+    checkpoint: Option<Checkpoint>,
+}
+
+impl Subscription {
+    /// Records that the event with `replay_id` has been processed.
+    ///
+    /// Events should be marked in the order they were received. Without a
+    /// replay store this does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if this triggers a save and the store fails. The
+    /// replay ID stays pending and is saved with the next commit.
+    pub async fn mark_processed(&mut self, replay_id: &[u8]) -> Result<(), Error> {
+        let Some(checkpoint) = &mut self.checkpoint else {
+            return Ok(());
+        };
+        checkpoint.pending = Some(replay_id.to_vec());
+        checkpoint.uncommitted += 1;
+        if checkpoint.is_due() {
+            checkpoint.commit().await?;
+        }
+        Ok(())
+    }
+
+    /// Saves the last processed replay ID now, regardless of batching.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store fails.
+    pub async fn commit(&mut self) -> Result<(), Error> {
+        match &mut self.checkpoint {
+            Some(checkpoint) => checkpoint.commit().await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Stream for Subscription {
+    type Item = Result<ConsumerEvent, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.task.abort();
+        if let Some(checkpoint) = &mut self.checkpoint {
+            if let Err(e) = checkpoint.commit_now() {
+                tracing::warn!("Failed to save replay ID on drop: {e}");
+            }
+        }
+    }
+}
+
+/// Replay IDs marked processed but not yet saved to the store.
+#[derive(Debug)]
+struct Checkpoint {
+    store: Arc<dyn ReplayStore>,
+    topic_name: String,
+    commit_every: usize,
+    commit_interval: Option<Duration>,
+    pending: Option<Vec<u8>>,
+    uncommitted: usize,
+    last_commit: Instant,
+}
+
+impl Checkpoint {
+    /// Returns whether the pending replay ID should be saved now.
+    fn is_due(&self) -> bool {
+        self.uncommitted >= self.commit_every
+            || self
+                .commit_interval
+                .is_some_and(|interval| self.last_commit.elapsed() >= interval)
+    }
+
+    /// Saves the pending replay ID on the blocking thread pool.
+    async fn commit(&mut self) -> Result<(), Error> {
+        let Some(replay_id) = self.pending.clone() else {
+            return Ok(());
+        };
+        let store = self.store.clone();
+        let topic_name = self.topic_name.clone();
+        let saved = tokio::task::spawn_blocking(move || store.save(&topic_name, &replay_id))
+            .await
+            .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+        self.committed(saved)
+    }
+
+    /// Saves the pending replay ID on the current thread.
+    fn commit_now(&mut self) -> Result<(), Error> {
+        let Some(replay_id) = &self.pending else {
+            return Ok(());
+        };
+        let saved = self.store.save(&self.topic_name, replay_id);
+        self.committed(saved)
+    }
+
+    /// Clears the pending replay ID once `saved` succeeded.
+    fn committed(&mut self, saved: Result<(), replay_store::Error>) -> Result<(), Error> {
+        saved.map_err(|e| Error::ReplayStore {
+            topic_name: self.topic_name.clone(),
+            source: e,
+        })?;
+        self.pending = None;
+        self.uncommitted = 0;
+        self.last_commit = Instant::now();
+        Ok(())
+    }
+}
+
+/// Builder for creating a [`Subscription`].
+#[derive(Debug)]
+pub struct Builder {
+    context: Option<Context>,
+    topic_name: Option<String>,
+    replay_preset: ReplayPreset,
+    replay_id: Option<Vec<u8>>,
+    num_requested: i32,
+    fallback_preset: ReplayPreset,
+    retry_policy: RetryPolicy,
+    replay_store: Option<Arc<dyn ReplayStore>>,
+    commit_every: usize,
+    commit_interval: Option<Duration>,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            context: None,
+            topic_name: None,
+            replay_preset: ReplayPreset::Latest,
+            replay_id: None,
+            num_requested: 100,
+            fallback_preset: ReplayPreset::Earliest,
+            retry_policy: RetryPolicy::default(),
+            replay_store: None,
+            commit_every: 1,
+            commit_interval: None,
+        }
+    }
+}
+
+impl Builder {
+    /// Creates a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the context used to subscribe.
+    pub fn context(mut self, context: Context) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Sets the topic to subscribe to.
+    pub fn topic_name(mut self, topic_name: impl Into<String>) -> Self {
+        self.topic_name = Some(topic_name.into());
+        self
+    }
+
+    /// Sets where the first subscription starts. Defaults to
+    /// [`ReplayPreset::Latest`].
+    pub fn replay_preset(mut self, replay_preset: ReplayPreset) -> Self {
+        self.replay_preset = replay_preset;
+        self
+    }
+
+    /// Starts the first subscription after the given replay ID.
+    pub fn replay_id(mut self, replay_id: Vec<u8>) -> Self {
+        self.replay_preset = ReplayPreset::Custom;
+        self.replay_id = Some(replay_id);
+        self
+    }
+
+    /// Sets the number of events requested per fetch. Defaults to 100.
+    pub fn num_requested(mut self, num_requested: i32) -> Self {
+        self.num_requested = num_requested;
+        self
+    }
+
+    /// Sets where to restart when the server rejects the stored replay ID.
+    /// Defaults to [`ReplayPreset::Earliest`].
+    pub fn fallback_preset(mut self, fallback_preset: ReplayPreset) -> Self {
+        self.fallback_preset = fallback_preset;
+        self
+    }
+
+    /// Sets the backoff between resubscribe attempts.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets the store for processed replay IDs.
+    ///
+    /// A replay ID found in the store takes precedence over
+    /// [`replay_preset`](Builder::replay_preset) and
+    /// [`replay_id`](Builder::replay_id).
+    pub fn replay_store(mut self, replay_store: Arc<dyn ReplayStore>) -> Self {
+        self.replay_store = Some(replay_store);
+        self
+    }
+
+    /// Saves to the replay store after this many processed events.
+    /// Defaults to 1.
+    pub fn commit_every(mut self, commit_every: usize) -> Self {
+        self.commit_every = commit_every.max(1);
+        self
+    }
+
+    /// Saves to the replay store when an event is marked processed and this
+    /// much time has passed since the last save, even if fewer than
+    /// [`commit_every`](Builder::commit_every) events are pending.
+    pub fn commit_interval(mut self, commit_interval: Duration) -> Self {
+        self.commit_interval = Some(commit_interval);
+        self
+    }
+
+    /// Starts the subscription in a background task.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the context or topic name is missing. If the
+    /// replay store cannot be read, the subscription yields
+    /// [`Error::ReplayStore`] and ends.
+    pub fn build(self) -> Result<Subscription, Error> {
+        let context = self
+            .context
+            .ok_or_else(|| Error::MissingRequiredAttribute("context".to_string()))?;
+        let topic_name = self
+            .topic_name
+            .ok_or_else(|| Error::MissingRequiredAttribute("topic_name".to_string()))?;
+
+        let checkpoint = self.replay_store.clone().map(|store| Checkpoint {
+            store,
+            topic_name: topic_name.clone(),
+            commit_every: self.commit_every,
+            commit_interval: self.commit_interval,
+            pending: None,
+            uncommitted: 0,
+            last_commit: Instant::now(),
+        });
+
+        let request = FetchRequest {
+            topic_name,
+            replay_preset: self.replay_preset.into(),
+            replay_id: self.replay_id.unwrap_or_default(),
+            num_requested: self.num_requested,
+            ..Default::default()
+        };
+        let (sender, events) = mpsc::channel(self.num_requested.max(1) as usize);
+        let task = tokio::spawn(
+            Worker {
+                context,
+                request,
+                replay_store: self.replay_store,
+                fallback_preset: self.fallback_preset,
+                retry_policy: self.retry_policy,
+                last_replay_id: None,
+                sender,
+            }
+            .run(),
+        );
+        Ok(Subscription {
+            events,
+            task,
+            checkpoint,
+        })
+    }
+}
+
+/// How a single Subscribe stream ended.
+enum Outcome {
+    /// The stream failed or was ended by the server, with or without an
+    /// error.
+    Ended {
+        error: Option<context::Error>,
+        /// Whether any response arrived before the stream ended.
+        received: bool,
+    },
+    /// The subscription is over, either because the caller dropped it or
+    /// because an unrecoverable error was already reported.
+    Stopped,
+}
+
+/// Background task that owns the gRPC stream and resubscribes on failure.
+struct Worker {
+    context: Context,
+    request: FetchRequest,
+    /// Store to load the starting replay ID from.
+    replay_store: Option<Arc<dyn ReplayStore>>,
+    fallback_preset: ReplayPreset,
+    retry_policy: RetryPolicy,
+    last_replay_id: Option<Vec<u8>>,
+    sender: mpsc::Sender<Result<ConsumerEvent, Error>>,
+}
+
+impl Worker {
+    async fn run(mut self) {
+        if let Err(error) = self.load_replay_id().await {
+            let _ = self.sender.send(Err(error)).await;
+            return;
+        }
+
+        let mut retries = 0;
+        loop {
+            let (error, received) = match self.stream().await {
+                Outcome::Ended { error, received } => (error, received),
+                Outcome::Stopped => return,
+            };
+            if received {
+                retries = 0;
+            }
+
+            let mut error = error.unwrap_or_else(|| {
+                context::Error::Tonic(Box::new(tonic::Status::unavailable(
+                    "Subscribe stream closed by server",
+                )))
+            });
+            let mut recovery = Recovery::for_error(&error);
+            if recovery == Recovery::Reauthenticate {
+                if let Err(e) = self.context.token_provider().refresh().await {
+                    error = context::Error::TokenRefresh { source: e };
+                    recovery = Recovery::for_error(&error);
+                }
+            }
+            match recovery {
+                Recovery::Fail => {
+                    let error = Error::Subscribe {
+                        topic_name: self.request.topic_name.clone(),
+                        source: error,
+                    };
+                    let _ = self.sender.send(Err(error)).await;
+                    return;
+                }
+                Recovery::Fallback => {
+                    tracing::warn!(
+                        "Replay ID rejected for {}, restarting from {}",
+                        self.request.topic_name,
+                        self.fallback_preset.as_str_name()
+                    );
+                    self.last_replay_id = None;
+                    self.request.replay_preset = self.fallback_preset.into();
+                    self.request.replay_id = Vec::new();
+                }
+                Recovery::Reauthenticate | Recovery::Resume => {}
+            }
+
+            retries += 1;
+            if !self.retry_policy.allows(retries) {
+                let error = Error::RetriesExhausted {
+                    topic_name: self.request.topic_name.clone(),
+                    retries: retries - 1,
+                    source: error,
+                };
+                let _ = self.sender.send(Err(error)).await;
+                return;
+            }
+            let backoff = self.retry_policy.backoff(retries);
+            tracing::info!(
+                "Resubscribing to {} in {backoff:?} after {error}",
+                self.request.topic_name,
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                _ = self.sender.closed() => return,
+            }
+
+            if let Some(replay_id) = &self.last_replay_id {
+                self.request.replay_preset = ReplayPreset::Custom.into();
+                self.request.replay_id = replay_id.clone();
+            }
+        }
+    }
+
+    /// Starts from the replay ID in the replay store, if it has one.
+    async fn load_replay_id(&mut self) -> Result<(), Error> {
+        let Some(store) = self.replay_store.clone() else {
+            return Ok(());
+        };
+        let topic_name = self.request.topic_name.clone();
+        let loaded = tokio::task::spawn_blocking(move || store.load(&topic_name))
+            .await
+            .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+        let stored_replay_id = loaded.map_err(|e| Error::ReplayStore {
+            topic_name: self.request.topic_name.clone(),
+            source: e,
+        })?;
+        if let Some(replay_id) = stored_replay_id {
+            self.request.replay_preset = ReplayPreset::Custom.into();
+            self.request.replay_id = replay_id;
+        }
+        Ok(())
+    }
+
+    /// Runs one Subscribe stream until it ends.
+    async fn stream(&mut self) -> Outcome {
+        let mut responses = match self.context.subscribe(self.request.clone()).await {
+            Ok(responses) => responses,
+            Err(e) => {
+                return Outcome::Ended {
+                    error: Some(e),
+                    received: false,
+                }
+            }
+        };
+
+        let mut received = false;
+        loop {
+            let response = tokio::select! {
+                response = responses.message() => response,
+                _ = self.sender.closed() => return Outcome::Stopped,
+            };
+            match response {
+                Ok(Some(response)) => {
+                    received = true;
+                    if response.events.is_empty() && !response.latest_replay_id.is_empty() {
+                        self.last_replay_id = Some(response.latest_replay_id);
+                    }
+                    for event in response.events {
+                        self.last_replay_id = Some(event.replay_id.clone());
+                        if self.sender.send(Ok(event)).await.is_err() {
+                            return Outcome::Stopped;
+                        }
+                    }
+                }
+                Ok(None) => {
+                    return Outcome::Ended {
+                        error: None,
+                        received,
+                    }
+                }
+                Err(status) => {
+                    return Outcome::Ended {
+                        error: Some(context::Error::Tonic(Box::new(status))),
+                        received,
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::replay_store::MemoryReplayStore;
+    use crate::pubsub::testing::{self, FakePubSub};
+    use futures_util::StreamExt;
+    use salesforce_pubsub_v1::eventbus::v1::FetchResponse;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const TOPIC: &str = "/event/Order__e";
+
+    fn events(replay_ids: &[u8]) -> FetchResponse {
+        FetchResponse {
+            events: replay_ids
+                .iter()
+                .map(|replay_id| ConsumerEvent {
+                    event: None,
+                    replay_id: vec![*replay_id],
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn fast_retries() -> RetryPolicy {
+        RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+            ..Default::default()
+        }
+    }
+
+    async fn subscribe(fake: &Arc<FakePubSub>, builder: Builder) -> Subscription {
+        let context = testing::serve(fake.clone()).await;
+        builder
+            .context(context)
+            .topic_name(TOPIC)
+            .retry_policy(fast_retries())
+            .build()
+            .unwrap()
+    }
+
+    async fn replay_ids(subscription: &mut Subscription, count: usize) -> Vec<Vec<u8>> {
+        let mut replay_ids = Vec::new();
+        for _ in 0..count {
+            replay_ids.push(subscription.next().await.unwrap().unwrap().replay_id);
+        }
+        replay_ids
+    }
+
+    #[tokio::test]
+    async fn test_build_requires_topic() {
+        let context = testing::serve(Arc::new(FakePubSub::default())).await;
+        let result = Builder::new().context(context).build();
+        assert!(matches!(
+            result,
+            Err(Error::MissingRequiredAttribute(attribute)) if attribute == "topic_name"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_resumes_after_last_event() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.push_session(vec![
+            Ok(events(&[1, 2])),
+            Err(tonic::Status::unavailable("connection reset")),
+        ]);
+        fake.push_session(vec![Ok(events(&[3]))]);
+        let mut subscription = subscribe(&fake, Builder::new().num_requested(5)).await;
+
+        let received = replay_ids(&mut subscription, 3).await;
+        assert_eq!(received, vec![vec![1], vec![2], vec![3]]);
+
+        let requests = fake.fetch_requests.lock().unwrap().clone();
+        assert_eq!(requests[0].replay_preset, ReplayPreset::Latest as i32);
+        assert_eq!(requests[1].replay_preset, ReplayPreset::Custom as i32);
+        assert_eq!(requests[1].replay_id, vec![2]);
+        assert_eq!(requests[1].num_requested, 5);
+    }
+
+    #[tokio::test]
+    async fn test_resumes_from_keepalive_after_server_close() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.push_session(vec![Ok(FetchResponse {
+            latest_replay_id: vec![9],
+            ..Default::default()
+        })]);
+        fake.push_session(vec![Ok(events(&[10]))]);
+        let mut subscription = subscribe(&fake, Builder::new()).await;
+
+        assert_eq!(replay_ids(&mut subscription, 1).await, vec![vec![10]]);
+        let requests = fake.fetch_requests.lock().unwrap().clone();
+        assert_eq!(requests[1].replay_preset, ReplayPreset::Custom as i32);
+        assert_eq!(requests[1].replay_id, vec![9]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_replay_id_falls_back() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.push_session(vec![Err(tonic::Status::invalid_argument(
+            "Replay ID validation failed",
+        ))]);
+        fake.push_session(vec![Ok(events(&[1]))]);
+        let builder = Builder::new()
+            .replay_id(vec![7])
+            .fallback_preset(ReplayPreset::Latest);
+        let mut subscription = subscribe(&fake, builder).await;
+
+        assert_eq!(replay_ids(&mut subscription, 1).await, vec![vec![1]]);
+        let requests = fake.fetch_requests.lock().unwrap().clone();
+        assert_eq!(requests[0].replay_preset, ReplayPreset::Custom as i32);
+        assert_eq!(requests[0].replay_id, vec![7]);
+        assert_eq!(requests[1].replay_preset, ReplayPreset::Latest as i32);
+        assert!(requests[1].replay_id.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_permanent_error_ends_stream() {
+        let fake = Arc::new(FakePubSub::default());
+        fake.push_session(vec![Err(tonic::Status::permission_denied("no access"))]);
+        let mut subscription = subscribe(&fake, Builder::new()).await;
+
+        let error = subscription.next().await.unwrap().unwrap_err();
+        assert!(matches!(error, Error::Subscribe { .. }));
+        assert!(subscription.next().await.is_none());
+        assert_eq!(fake.fetch_requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_revoked_refresh_token_ends_stream() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "access_token": "token_1",
+                    "token_type": "Bearer",
+                    "refresh_token": "refresh_token_1"
+                })),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                    "error": "invalid_grant",
+                    "error_description": "expired access/refresh token"
+                })),
+            )
+            .mount(&server)
+            .await;
+        let client = crate::client::Builder::new()
+            .credentials(crate::client::Credentials {
+                client_id: "test_id".to_string(),
+                client_secret: Some("test_secret".into()),
+                instance_url: server.uri(),
+                tenant_id: Some("test_tenant".to_string()),
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+
+        let fake = Arc::new(FakePubSub::default());
+        fake.push_session(vec![Err(tonic::Status::unauthenticated("expired"))]);
+        let channel = tonic::transport::Endpoint::from_shared(testing::listen(fake.clone()).await)
+            .unwrap()
+            .connect_lazy();
+        let context = Context::new(channel, client).unwrap();
+        let mut subscription = Builder::new()
+            .context(context)
+            .topic_name(TOPIC)
+            .retry_policy(fast_retries())
+            .build()
+            .unwrap();
+
+        let error = subscription.next().await.unwrap().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Subscribe {
+                source: context::Error::TokenRefresh { .. },
+                ..
+            }
+        ));
+        assert!(subscription.next().await.is_none());
+        assert_eq!(fake.fetch_requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retries_exhausted() {
+        let fake = Arc::new(FakePubSub::default());
+        for _ in 0..3 {
+            fake.push_session(vec![Err(tonic::Status::unavailable("down"))]);
+        }
+        let builder = Builder::new().retry_policy(RetryPolicy {
+            max_retries: Some(2),
+            ..fast_retries()
+        });
+        let context = testing::serve(fake.clone()).await;
+        let mut subscription = builder.context(context).topic_name(TOPIC).build().unwrap();
+
+        let error = subscription.next().await.unwrap().unwrap_err();
+        assert!(matches!(error, Error::RetriesExhausted { retries: 2, .. }));
+        assert_eq!(fake.fetch_requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_starts_from_stored_replay_id() {
+        let fake = Arc::new(FakePubSub::default());
+        let store = Arc::new(MemoryReplayStore::new());
+        store.save(TOPIC, &[4]).unwrap();
+        let builder = Builder::new()
+            .replay_preset(ReplayPreset::Earliest)
+            .replay_store(store);
+        fake.push_session(vec![Ok(events(&[5]))]);
+        let mut subscription = subscribe(&fake, builder).await;
+
+        assert_eq!(replay_ids(&mut subscription, 1).await, vec![vec![5]]);
+        let requests = fake.fetch_requests.lock().unwrap().clone();
+        assert_eq!(requests[0].replay_preset, ReplayPreset::Custom as i32);
+        assert_eq!(requests[0].replay_id, vec![4]);
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_replay_store_ends_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replay.json");
+        std::fs::write(&path, "not json").unwrap();
+        let fake = Arc::new(FakePubSub::default());
+        let builder =
+            Builder::new().replay_store(Arc::new(replay_store::FileReplayStore::new(path)));
+        let mut subscription = subscribe(&fake, builder).await;
+
+        let error = subscription.next().await.unwrap().unwrap_err();
+        assert!(matches!(error, Error::ReplayStore { .. }));
+        assert!(subscription.next().await.is_none());
+        assert!(fake.fetch_requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mark_processed_commits_in_batches() {
+        let fake = Arc::new(FakePubSub::default());
+        let store = Arc::new(MemoryReplayStore::new());
+        fake.push_session(vec![Ok(events(&[1, 2, 3]))]);
+        let builder = Builder::new().replay_store(store.clone()).commit_every(2);
+        let mut subscription = subscribe(&fake, builder).await;
+
+        let received = replay_ids(&mut subscription, 3).await;
+        subscription.mark_processed(&received[0]).await.unwrap();
+        assert_eq!(store.load(TOPIC).unwrap(), None);
+        subscription.mark_processed(&received[1]).await.unwrap();
+        assert_eq!(store.load(TOPIC).unwrap(), Some(vec![2]));
+        subscription.mark_processed(&received[2]).await.unwrap();
+        assert_eq!(store.load(TOPIC).unwrap(), Some(vec![2]));
+
+        drop(subscription);
+        assert_eq!(store.load(TOPIC).unwrap(), Some(vec![3]));
+    }
+
+    #[tokio::test]
+    async fn test_commit_interval_saves_early() {
+        let fake = Arc::new(FakePubSub::default());
+        let store = Arc::new(MemoryReplayStore::new());
+        let builder = Builder::new()
+            .replay_store(store.clone())
+            .commit_every(100)
+            .commit_interval(Duration::ZERO);
+        let mut subscription = subscribe(&fake, builder).await;
+
+        subscription.mark_processed(&[8]).await.unwrap();
+        assert_eq!(store.load(TOPIC).unwrap(), Some(vec![8]));
+    }
+
+    #[test]
+    fn test_recovery_classification() {
+        assert_eq!(
+            Recovery::of(&tonic::Status::unavailable("")),
+            Recovery::Resume
+        );
+        assert_eq!(
+            Recovery::of(&tonic::Status::unauthenticated("")),
+            Recovery::Reauthenticate
+        );
+        assert_eq!(Recovery::of(&tonic::Status::not_found("")), Recovery::Fail);
+        let mut status = tonic::Status::invalid_argument("bad request");
+        status.metadata_mut().insert(
+            "error-code",
+            "sfdc.platform.eventbus.grpc.subscription.fetch.replayid.corrupted"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(Recovery::of(&status), Recovery::Fallback);
+    }
+
+    #[test]
+    fn test_recovery_for_context_errors() {
+        let error = context::Error::TokenRefresh {
+            source: crate::token::provider::Error::Refresh {
+                source: crate::client::Error::TokenExchange(Box::new(oauth2::RequestTokenError::<
+                    reqwest::Error,
+                    oauth2::basic::BasicErrorResponse,
+                >::Other(
+                    "connection reset".to_string(),
+                ))),
+            },
+        };
+        assert_eq!(Recovery::for_error(&error), Recovery::Resume);
+        let error = context::Error::TokenRefresh {
+            source: crate::token::provider::Error::Refresh {
+                source: crate::client::Error::NotConnected,
+            },
+        };
+        assert_eq!(Recovery::for_error(&error), Recovery::Fail);
+        let error = context::Error::TokenRefresh {
+            source: crate::token::provider::Error::MissingTokenResponse(),
+        };
+        assert_eq!(Recovery::for_error(&error), Recovery::Fail);
+        let error = context::Error::Tonic(Box::new(tonic::Status::unauthenticated("")));
+        assert_eq!(Recovery::for_error(&error), Recovery::Reauthenticate);
+        let error = context::Error::MissingRequiredAttribute("tenant_id".to_string());
+        assert_eq!(Recovery::for_error(&error), Recovery::Fail);
+    }
+}
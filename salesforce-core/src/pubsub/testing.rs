@@ -0,0 +1,332 @@
+use crate::client;
+use crate::pubsub::context::Context;
+use salesforce_pubsub_v1::eventbus::v1::pub_sub_server::{PubSub, PubSubServer};
+use salesforce_pubsub_v1::eventbus::v1::{
+    CommitReplayResponse, Error as EventBusError, ErrorCode, FetchRequest, FetchResponse,
+    ManagedFetchRequest, ManagedFetchResponse, PublishRequest, PublishResponse, PublishResult,
+    SchemaInfo, SchemaRequest, TopicInfo, TopicRequest,
+};
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+type ResponseStream<T> = Pin<Box<dyn tokio_stream::Stream<Item = Result<T, tonic::Status>> + Send>>;
+
+/// In-process Pub/Sub server for tests.
+#[derive(Debug, Default)]
+pub(crate) struct FakePubSub {
+    /// Schema JSON by schema ID.
+    pub(crate) schemas: Mutex<HashMap<String, String>>,
+    /// Topic metadata by topic name.
+    pub(crate) topics: Mutex<HashMap<String, TopicInfo>>,
+    /// Number of GetSchema calls received.
+    pub(crate) get_schema_calls: AtomicUsize,
+    /// Number of GetTopic calls received.
+    pub(crate) get_topic_calls: AtomicUsize,
+    /// Publish requests received.
+    pub(crate) published: Mutex<Vec<PublishRequest>>,
+    /// IDs of events whose publish results carry an error.
+    pub(crate) rejected_events: Mutex<Vec<String>>,
+    /// IDs of events rejected the given number of times before succeeding.
+    pub(crate) transient_rejections: Mutex<HashMap<String, usize>>,
+    /// Requests received on PublishStream calls.
+    pub(crate) stream_published: Arc<Mutex<Vec<PublishRequest>>>,
+    /// Statuses returned by the next Publish or PublishStream calls, which
+    /// are not recorded.
+    pub(crate) publish_failures: Mutex<VecDeque<tonic::Status>>,
+    /// First fetch request of each Subscribe stream.
+    pub(crate) fetch_requests: Arc<Mutex<Vec<FetchRequest>>>,
+    /// Later fetch requests on Subscribe streams, which only add credits.
+    pub(crate) top_up_requests: Arc<Mutex<Vec<FetchRequest>>>,
+    /// Scripted Subscribe streams, one per call. Each stream sends its items
+    /// and then ends; once they run out, streams stay open without sending.
+    pub(crate) subscribe_sessions: Mutex<VecDeque<Vec<Result<FetchResponse, tonic::Status>>>>,
+    /// Requests received on ManagedSubscribe streams.
+    pub(crate) managed_requests: Arc<Mutex<Vec<ManagedFetchRequest>>>,
+    /// Scripted responses for ManagedSubscribe streams, one list per call.
+    pub(crate) managed_sessions: Mutex<VecDeque<Vec<ManagedFetchResponse>>>,
+    /// Replay IDs whose commits are rejected.
+    pub(crate) rejected_commits: Arc<Mutex<Vec<Vec<u8>>>>,
+    /// Fails the ManagedSubscribe stream when a commit arrives.
+    pub(crate) fail_managed_commits: Arc<AtomicBool>,
+}
+
+impl FakePubSub {
+    pub(crate) fn with_schema(self, schema_id: &str, schema_json: &str) -> Self {
+        self.schemas
+            .lock()
+            .unwrap()
+            .insert(schema_id.to_string(), schema_json.to_string());
+        self
+    }
+
+    pub(crate) fn with_topic(self, topic_name: &str, schema_id: &str) -> Self {
+        self.topics.lock().unwrap().insert(
+            topic_name.to_string(),
+            TopicInfo {
+                topic_name: topic_name.to_string(),
+                can_publish: true,
+                can_subscribe: true,
+                schema_id: schema_id.to_string(),
+                ..Default::default()
+            },
+        );
+        self
+    }
+
+    /// Scripts the items sent on the next unscripted Subscribe stream.
+    pub(crate) fn push_session(&self, items: Vec<Result<FetchResponse, tonic::Status>>) {
+        self.subscribe_sessions.lock().unwrap().push_back(items);
+    }
+
+    /// Scripts the responses sent on the next ManagedSubscribe stream.
+    pub(crate) fn push_managed_session(&self, responses: Vec<ManagedFetchResponse>) {
+        self.managed_sessions.lock().unwrap().push_back(responses);
+    }
+}
+
+#[tonic::async_trait]
+impl PubSub for FakePubSub {
+    type SubscribeStream = ResponseStream<FetchResponse>;
+    type PublishStreamStream = ResponseStream<PublishResponse>;
+    type ManagedSubscribeStream = ResponseStream<ManagedFetchResponse>;
+
+    async fn subscribe(
+        &self,
+        request: tonic::Request<tonic::Streaming<FetchRequest>>,
+    ) -> Result<tonic::Response<Self::SubscribeStream>, tonic::Status> {
+        let mut requests = request.into_inner();
+        let first = requests
+            .message()
+            .await?
+            .ok_or_else(|| tonic::Status::invalid_argument("no fetch request"))?;
+        self.fetch_requests.lock().unwrap().push(first);
+        let top_up_requests = self.top_up_requests.clone();
+        tokio::spawn(async move {
+            while let Ok(Some(request)) = requests.message().await {
+                top_up_requests.lock().unwrap().push(request);
+            }
+        });
+
+        match self.subscribe_sessions.lock().unwrap().pop_front() {
+            Some(items) => Ok(tonic::Response::new(Box::pin(tokio_stream::iter(items)))),
+            None => Ok(tonic::Response::new(Box::pin(tokio_stream::pending()))),
+        }
+    }
+
+    async fn get_schema(
+        &self,
+        request: tonic::Request<SchemaRequest>,
+    ) -> Result<tonic::Response<SchemaInfo>, tonic::Status> {
+        self.get_schema_calls.fetch_add(1, Ordering::SeqCst);
+        let schema_id = request.into_inner().schema_id;
+        let schema_json = self
+            .schemas
+            .lock()
+            .unwrap()
+            .get(&schema_id)
+            .cloned()
+            .ok_or_else(|| tonic::Status::not_found(format!("schema {schema_id}")))?;
+        Ok(tonic::Response::new(SchemaInfo {
+            schema_json,
+            schema_id,
+            rpc_id: "rpc".to_string(),
+        }))
+    }
+
+    async fn get_topic(
+        &self,
+        request: tonic::Request<TopicRequest>,
+    ) -> Result<tonic::Response<TopicInfo>, tonic::Status> {
+        self.get_topic_calls.fetch_add(1, Ordering::SeqCst);
+        let topic_name = request.into_inner().topic_name;
+        self.topics
+            .lock()
+            .unwrap()
+            .get(&topic_name)
+            .cloned()
+            .map(tonic::Response::new)
+            .ok_or_else(|| tonic::Status::not_found(format!("topic {topic_name}")))
+    }
+
+    async fn publish(
+        &self,
+        request: tonic::Request<PublishRequest>,
+    ) -> Result<tonic::Response<PublishResponse>, tonic::Status> {
+        if let Some(status) = self.publish_failures.lock().unwrap().pop_front() {
+            return Err(status);
+        }
+        let request = request.into_inner();
+        let mut published = self.published.lock().unwrap();
+        let offset = published.iter().map(|r| r.events.len()).sum::<usize>();
+        let rejected_events = self.rejected_events.lock().unwrap();
+        let mut transient_rejections = self.transient_rejections.lock().unwrap();
+        let results = request
+            .events
+            .iter()
+            .enumerate()
+            .map(|(index, event)| {
+                let transient = transient_rejections
+                    .get_mut(&event.id)
+                    .filter(|remaining| **remaining > 0)
+                    .map(|remaining| *remaining -= 1)
+                    .is_some();
+                let error =
+                    (transient || rejected_events.contains(&event.id)).then(|| EventBusError {
+                        code: ErrorCode::Publish.into(),
+                        msg: "event rejected".to_string(),
+                    });
+                PublishResult {
+                    replay_id: ((offset + index) as u64).to_be_bytes().to_vec(),
+                    error,
+                    correlation_key: event.id.clone(),
+                }
+            })
+            .collect();
+        let schema_id = request
+            .events
+            .first()
+            .map(|event| event.schema_id.clone())
+            .unwrap_or_default();
+        published.push(request);
+        Ok(tonic::Response::new(PublishResponse {
+            results,
+            schema_id,
+            rpc_id: "rpc".to_string(),
+        }))
+    }
+
+    async fn publish_stream(
+        &self,
+        request: tonic::Request<tonic::Streaming<PublishRequest>>,
+    ) -> Result<tonic::Response<Self::PublishStreamStream>, tonic::Status> {
+        if let Some(status) = self.publish_failures.lock().unwrap().pop_front() {
+            return Err(status);
+        }
+        let mut requests = request.into_inner();
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let stream_published = self.stream_published.clone();
+        // The response stream ends once the client half-closes.
+        tokio::spawn(async move {
+            while let Ok(Some(request)) = requests.message().await {
+                let results = request
+                    .events
+                    .iter()
+                    .map(|event| PublishResult {
+                        replay_id: event.id.as_bytes().to_vec(),
+                        error: None,
+                        correlation_key: event.id.clone(),
+                    })
+                    .collect();
+                stream_published.lock().unwrap().push(request);
+                let _ = sender.send(Ok(PublishResponse {
+                    results,
+                    schema_id: String::new(),
+                    rpc_id: "rpc".to_string(),
+                }));
+            }
+        });
+        Ok(tonic::Response::new(Box::pin(
+            tokio_stream::wrappers::UnboundedReceiverStream::new(receiver),
+        )))
+    }
+
+    async fn managed_subscribe(
+        &self,
+        request: tonic::Request<tonic::Streaming<ManagedFetchRequest>>,
+    ) -> Result<tonic::Response<Self::ManagedSubscribeStream>, tonic::Status> {
+        let mut requests = request.into_inner();
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let scripted = self
+            .managed_sessions
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_default();
+        for response in scripted {
+            let _ = sender.send(Ok(response));
+        }
+
+        let managed_requests = self.managed_requests.clone();
+        let rejected_commits = self.rejected_commits.clone();
+        let fail_managed_commits = self.fail_managed_commits.clone();
+        tokio::spawn(async move {
+            while let Ok(Some(request)) = requests.message().await {
+                managed_requests.lock().unwrap().push(request.clone());
+                let Some(commit) = request.commit_replay_id_request else {
+                    continue;
+                };
+                if fail_managed_commits.load(Ordering::SeqCst) {
+                    let _ = sender.send(Err(tonic::Status::internal("commit failed")));
+                    return;
+                }
+                let error = rejected_commits
+                    .lock()
+                    .unwrap()
+                    .contains(&commit.replay_id)
+                    .then(|| EventBusError {
+                        code: ErrorCode::Commit.into(),
+                        msg: "replay ID not committed".to_string(),
+                    });
+                let _ = sender.send(Ok(ManagedFetchResponse {
+                    commit_response: Some(CommitReplayResponse {
+                        commit_request_id: commit.commit_request_id,
+                        replay_id: commit.replay_id,
+                        error,
+                        process_time: 1,
+                    }),
+                    ..Default::default()
+                }));
+            }
+        });
+        Ok(tonic::Response::new(Box::pin(
+            tokio_stream::wrappers::UnboundedReceiverStream::new(receiver),
+        )))
+    }
+}
+
+/// Serves `fake` on a local port and returns a context connected to it.
+pub(crate) async fn serve(fake: Arc<FakePubSub>) -> Context {
+    let channel = tonic::transport::Endpoint::from_shared(listen(fake).await)
+        .unwrap()
+        .connect_lazy();
+    Context::new(channel, connected_client()).unwrap()
+}
+
+/// Serves `fake` on a local port and returns its URL.
+pub(crate) async fn listen(fake: Arc<FakePubSub>) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(PubSubServer::from_arc(fake))
+            .serve_with_incoming(tonic::transport::server::TcpIncoming::from(listener)),
+    );
+    format!("http://{addr}")
+}
+
+/// Returns a client that looks connected without talking to Salesforce.
+pub(crate) fn connected_client() -> client::Client {
+    let mut client = client::Builder::new()
+        .credentials(client::Credentials {
+            client_id: "client_id".to_string(),
+            instance_url: "https://test.my.salesforce.com".to_string(),
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    client.token_result = Some(client::TokenResponse::new(
+        oauth2::AccessToken::new("access_token".to_string()),
+        oauth2::basic::BasicTokenType::Bearer,
+        client::SalesforceTokenFields {
+            id: Some(
+                "https://login.salesforce.com/id/00Dxx0000001gPLEAY/005xx000001SwiUAAS".to_string(),
+            ),
+            ..Default::default()
+        },
+    ));
+    client.instance_url = Some("https://test.my.salesforce.com".to_string());
+    client.tenant_id = Some("00Dxx0000001gPLEAY".to_string());
+    client
+}
@@ -0,0 +1,624 @@
+use crate::pubsub::context;
+use crate::pubsub::ids::new_event_id;
+use crate::pubsub::schema_cache::{self, CachedSchema, SchemaCache};
+use futures_util::Stream;
+use salesforce_pubsub_v1::eventbus::v1::{
+    ConsumerEvent, FetchRequest, ProducerEvent, PublishRequest, PublishResult, TopicRequest,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Errors that can occur while publishing or consuming typed events.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The GetTopic call failed.
+    #[error("Failed to look up topic {topic_name}: {source}")]
+    Topic {
+        /// Name of the topic.
+        topic_name: String,
+        #[source]
+        source: context::Error,
+    },
+    /// Looking up or applying the topic schema failed.
+    #[error(transparent)]
+    Schema(#[from] schema_cache::Error),
+    /// The event could not be serialized.
+    #[error("Failed to serialize event: {source}")]
+    Serialize {
+        #[source]
+        source: serde_json::Error,
+    },
+    /// The event does not match the topic schema.
+    #[error("Event does not match schema {schema_id} of {topic_name}: {source}")]
+    SchemaMismatch {
+        /// Name of the topic.
+        topic_name: String,
+        /// ID of the topic schema.
+        schema_id: String,
+        #[source]
+        source: crate::pubsub::avro::Error,
+    },
+    /// A consumed event could not be deserialized into the target type.
+    #[error("Failed to deserialize event {event_id} with schema {schema_id}: {source}")]
+    Deserialize {
+        /// ID of the event.
+        event_id: String,
+        /// ID of the schema the event was written with.
+        schema_id: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// The Publish call failed.
+    #[error("Failed to publish to {topic_name}: {source}")]
+    Publish {
+        /// Name of the topic.
+        topic_name: String,
+        #[source]
+        source: context::Error,
+    },
+    /// The Subscribe call failed or the stream returned an error.
+    #[error("Subscription to {topic_name} failed: {source}")]
+    Subscribe {
+        /// Name of the topic.
+        topic_name: String,
+        #[source]
+        source: context::Error,
+    },
+}
+
+/// Stream of events returned by [`Topic::subscribe`].
+pub type EventStream<T> = Pin<Box<dyn Stream<Item = Result<Event<T>, Error>> + Send>>;
+
+/// Event consumed from a [`Topic`].
+#[derive(Debug, Clone)]
+pub struct Event<T> {
+    /// Event ID assigned by the publisher.
+    pub id: String,
+    /// Replay ID of the event, used to resume a subscription after it.
+    pub replay_id: Vec<u8>,
+    /// ID of the schema the event was written with.
+    pub schema_id: String,
+    /// Deserialized event payload.
+    pub payload: T,
+}
+
+/// Topic whose events are Rust types.
+///
+/// Events are converted to and from the topic's Avro schema through serde,
+/// so field names must match the schema, e.g. with
+/// `#[serde(rename = "Order_Number__c")]`. Optional fields map to
+/// `Option`. When publishing, the `CreatedDate` and `CreatedById` fields that
+/// every platform event schema requires are filled in unless the type sets
+/// them.
+///
+/// The topic's schema ID is looked up once and shared by clones of the
+/// handle. It is looked up again after an event does not match the schema or
+/// a Publish call fails, which is how a changed schema shows up.
+///
+/// # Examples
+///
+/// ```no_run
+/// use futures_util::StreamExt;
+/// use salesforce_core::pubsub::context::Context;
+/// use salesforce_core::pubsub::schema_cache::SchemaCache;
+/// use salesforce_core::pubsub::typed::Topic;
+/// use salesforce_pubsub_v1::eventbus::v1::{FetchRequest, ReplayPreset};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct OrderPlaced {
+///     #[serde(rename = "Order_Number__c")]
+///     order_number: Option<String>,
+/// }
+///
+/// # async fn example(context: Context) -> Result<(), Box<dyn std::error::Error>> {
+/// let topic = Topic::<OrderPlaced>::new(SchemaCache::new(context), "/event/Order_Placed__e");
+///
+/// topic
+///     .publish(&[OrderPlaced { order_number: Some("ORD-1001".to_string()) }])
+///     .await?;
+///
+/// let mut events = topic
+///     .subscribe(FetchRequest {
+///         replay_preset: ReplayPreset::Latest.into(),
+///         num_requested: 100,
+///         ..Default::default()
+///     })
+///     .await?;
+/// while let Some(event) = events.next().await {
+///     println!("{:?}", event?.payload.order_number);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Topic<T> {
+    cache: SchemaCache,
+    topic_name: String,
+    /// Schema ID from the last GetTopic call.
+    schema_id: Arc<Mutex<Option<String>>>,
+    _event: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Topic<T> {
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache.clone(),
+            topic_name: self.topic_name.clone(),
+            schema_id: self.schema_id.clone(),
+            _event: PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Topic<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Topic")
+            .field("topic_name", &self.topic_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Topic<T> {
+    /// Creates a typed handle for `topic_name`, e.g. `/event/Order_Placed__e`.
+    pub fn new(cache: SchemaCache, topic_name: impl Into<String>) -> Self {
+        Self {
+            cache,
+            topic_name: topic_name.into(),
+            schema_id: Arc::new(Mutex::new(None)),
+            _event: PhantomData,
+        }
+    }
+
+    /// Returns the topic name.
+    pub fn topic_name(&self) -> &str {
+        &self.topic_name
+    }
+
+    /// Returns the current schema of the topic.
+    ///
+    /// The topic is only looked up the first time, and again after the
+    /// cached schema ID was invalidated.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the topic or its schema cannot be looked up.
+    pub async fn schema(&self) -> Result<Arc<CachedSchema>, Error> {
+        let cached = self.schema_id.lock().unwrap().clone();
+        if let Some(schema_id) = cached {
+            return Ok(self.cache.get(&schema_id).await?);
+        }
+
+        let topic_info = self
+            .cache
+            .context()
+            .clone()
+            .get_topic(TopicRequest {
+                topic_name: self.topic_name.clone(),
+            })
+            .await
+            .map_err(|e| Error::Topic {
+                topic_name: self.topic_name.clone(),
+                source: e,
+            })?
+            .into_inner();
+        let schema = self.cache.get(&topic_info.schema_id).await?;
+        *self.schema_id.lock().unwrap() = Some(topic_info.schema_id);
+        Ok(schema)
+    }
+
+    /// Forgets the cached schema ID so the next call looks up the topic.
+    fn invalidate_schema_id(&self) {
+        self.schema_id.lock().unwrap().take();
+    }
+
+    /// Invalidates the cached schema ID if `error` may be caused by a
+    /// schema change, then returns the error.
+    fn check_error(&self, error: Error) -> Error {
+        if matches!(error, Error::SchemaMismatch { .. } | Error::Publish { .. }) {
+            self.invalidate_schema_id();
+        }
+        error
+    }
+}
+
+impl<T: Serialize> Topic<T> {
+    /// Publishes events and returns one result per event, in order.
+    ///
+    /// Results with an `error` were rejected by the server; the others carry
+    /// the replay ID the event was stored with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an event does not match the topic schema, in
+    /// which case nothing is published, or if the Publish call fails.
+    pub async fn publish(&self, events: &[T]) -> Result<Vec<PublishResult>, Error> {
+        let schema = self.schema().await?;
+        let user_id = self.user_id().await;
+        let events = events
+            .iter()
+            .map(|event| self.producer_event(&schema, event, user_id.as_deref()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| self.check_error(e))?;
+
+        Ok(self
+            .cache
+            .context()
+            .clone()
+            .publish(PublishRequest {
+                topic_name: self.topic_name.clone(),
+                events,
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| {
+                self.check_error(Error::Publish {
+                    topic_name: self.topic_name.clone(),
+                    source: e,
+                })
+            })?
+            .into_inner()
+            .results)
+    }
+
+    /// Encodes an event with the topic's current schema.
+    ///
+    /// The event gets a new random ID, which the server echoes back as the
+    /// `correlation_key` of its [`PublishResult`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the schema cannot be looked up or the event does
+    /// not match it.
+    pub async fn encode(&self, event: &T) -> Result<ProducerEvent, Error> {
+        let schema = self.schema().await?;
+        self.producer_event(&schema, event, self.user_id().await.as_deref())
+            .map_err(|e| self.check_error(e))
+    }
+
+    fn producer_event(
+        &self,
+        schema: &CachedSchema,
+        event: &T,
+        user_id: Option<&str>,
+    ) -> Result<ProducerEvent, Error> {
+        let mut json = serde_json::to_value(event).map_err(|e| Error::Serialize { source: e })?;
+        fill_platform_fields(schema, &mut json, user_id);
+
+        let mismatch = |e| Error::SchemaMismatch {
+            topic_name: self.topic_name.clone(),
+            schema_id: schema.schema_id.clone(),
+            source: e,
+        };
+        let value = schema.schema.value_from_json(&json).map_err(mismatch)?;
+        let payload = schema.schema.encode(&value).map_err(mismatch)?;
+
+        Ok(ProducerEvent {
+            id: new_event_id(),
+            schema_id: schema.schema_id.clone(),
+            payload,
+            headers: Vec::new(),
+        })
+    }
+
+    /// Returns the ID of the authenticated user, used for `CreatedById`.
+    async fn user_id(&self) -> Option<String> {
+        let client = self.cache.context().token_provider().client().await;
+        client
+            .token_result?
+            .extra_fields()
+            .user_id()
+            .map(str::to_string)
+    }
+}
+
+impl<T: DeserializeOwned + Send + 'static> Topic<T> {
+    /// Subscribes to the topic and returns a stream of deserialized events.
+    ///
+    /// The `topic_name` of `request` is replaced with this topic's name. The
+    /// stream ends when the server closes the subscription; an error item is
+    /// returned for each event that cannot be decoded, without ending the
+    /// stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Subscribe`] if the Subscribe call fails.
+    pub async fn subscribe(&self, request: FetchRequest) -> Result<EventStream<T>, Error> {
+        let topic_name = self.topic_name.clone();
+        let subscribe_error = |e| Error::Subscribe {
+            topic_name: topic_name.clone(),
+            source: e,
+        };
+        let responses = self
+            .cache
+            .context()
+            .clone()
+            .subscribe(FetchRequest {
+                topic_name: self.topic_name.clone(),
+                ..request
+            })
+            .await
+            .map_err(subscribe_error)?;
+
+        let state = (responses, self.cache.decoder(), VecDeque::new());
+        Ok(Box::pin(futures_util::stream::unfold(
+            state,
+            move |(mut responses, mut decoder, mut pending)| {
+                let topic_name = topic_name.clone();
+                async move {
+                    loop {
+                        if let Some(event) = pending.pop_front() {
+                            let event = decode_event(&mut decoder, event).await;
+                            return Some((event, (responses, decoder, pending)));
+                        }
+                        match responses.message().await {
+                            Ok(Some(response)) => pending.extend(response.events),
+                            Ok(None) => return None,
+                            Err(status) => {
+                                let error = Error::Subscribe {
+                                    topic_name,
+                                    source: context::Error::Tonic(Box::new(status)),
+                                };
+                                return Some((Err(error), (responses, decoder, pending)));
+                            }
+                        }
+                    }
+                }
+            },
+        )))
+    }
+}
+
+async fn decode_event<T: DeserializeOwned>(
+    decoder: &mut schema_cache::EventDecoder,
+    event: ConsumerEvent,
+) -> Result<Event<T>, Error> {
+    let producer_event = event.event.unwrap_or_default();
+    let decoded = decoder.decode(&producer_event).await?;
+    let payload =
+        serde_json::from_value(JsonValue::from(decoded.value)).map_err(|e| Error::Deserialize {
+            event_id: producer_event.id.clone(),
+            schema_id: producer_event.schema_id.clone(),
+            source: e,
+        })?;
+    Ok(Event {
+        id: producer_event.id,
+        replay_id: event.replay_id,
+        schema_id: producer_event.schema_id,
+        payload,
+    })
+}
+
+/// Sets the `CreatedDate` and `CreatedById` fields of platform events when
+/// the schema has them and the serialized event does not.
+fn fill_platform_fields(schema: &CachedSchema, json: &mut JsonValue, user_id: Option<&str>) {
+    let (Some(record), JsonValue::Object(object)) = (schema_record(schema), json) else {
+        return;
+    };
+    if record.field("CreatedDate").is_some() && !object.contains_key("CreatedDate") {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        object.insert("CreatedDate".to_string(), JsonValue::from(now as u64));
+    }
+    if let Some(user_id) = user_id {
+        if record.field("CreatedById").is_some() && !object.contains_key("CreatedById") {
+            object.insert("CreatedById".to_string(), JsonValue::from(user_id));
+        }
+    }
+}
+
+fn schema_record(schema: &CachedSchema) -> Option<&crate::pubsub::avro::Record> {
+    match schema.schema.root() {
+        crate::pubsub::avro::Node::Record(record) => Some(record),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::testing::{self, FakePubSub};
+    use futures_util::StreamExt;
+    use salesforce_pubsub_v1::eventbus::v1::FetchResponse;
+    use serde::Deserialize;
+    use std::sync::atomic::Ordering;
+
+    const SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "Order_Placed__e",
+        "namespace": "com.sforce.eventbus",
+        "fields": [
+            {"name": "CreatedDate", "type": "long"},
+            {"name": "CreatedById", "type": "string"},
+            {"name": "Order_Number__c", "type": ["null", "string"], "default": null},
+            {"name": "Amount__c", "type": ["null", "double"], "default": null}
+        ]
+    }"#;
+
+    const TOPIC: &str = "/event/Order_Placed__e";
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct OrderPlaced {
+        #[serde(rename = "Order_Number__c")]
+        order_number: Option<String>,
+        #[serde(rename = "Amount__c")]
+        amount: Option<f64>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Unrelated {
+        #[serde(rename = "Color__c")]
+        color: String,
+    }
+
+    fn order_placed() -> OrderPlaced {
+        OrderPlaced {
+            order_number: Some("ORD-1001".to_string()),
+            amount: None,
+        }
+    }
+
+    async fn topic<T>(fake: FakePubSub) -> (Arc<FakePubSub>, Topic<T>) {
+        let fake = Arc::new(
+            fake.with_schema("schema-1", SCHEMA)
+                .with_topic(TOPIC, "schema-1"),
+        );
+        let context = testing::serve(fake.clone()).await;
+        (fake, Topic::new(SchemaCache::new(context), TOPIC))
+    }
+
+    #[tokio::test]
+    async fn test_publish_encodes_events() {
+        let (fake, topic) = topic(FakePubSub::default()).await;
+        let order = OrderPlaced {
+            order_number: Some("ORD-1001".to_string()),
+            amount: Some(149.99),
+        };
+
+        let results = topic.publish(&[order]).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].error.is_none());
+
+        let published = fake.published.lock().unwrap();
+        let event = &published[0].events[0];
+        assert_eq!(published[0].topic_name, TOPIC);
+        assert_eq!(event.schema_id, "schema-1");
+        assert_eq!(results[0].correlation_key, event.id);
+
+        let decoded = crate::pubsub::avro::Schema::parse(SCHEMA)
+            .unwrap()
+            .decode(&event.payload)
+            .unwrap();
+        assert_eq!(
+            decoded.field("CreatedById").and_then(|v| v.as_str()),
+            Some("005xx000001SwiUAAS")
+        );
+        assert!(decoded.field("CreatedDate").and_then(|v| v.as_long()) > Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_publish_schema_mismatch() {
+        let (fake, topic) = topic(FakePubSub::default()).await;
+        let error = topic
+            .publish(&[Unrelated {
+                color: "red".to_string(),
+            }])
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::SchemaMismatch { .. }));
+        assert!(error.to_string().contains("Color__c"), "{error}");
+        assert!(fake.published.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_publish_caches_schema_id() {
+        let (fake, topic) = topic::<OrderPlaced>(FakePubSub::default()).await;
+        topic.publish(&[order_placed()]).await.unwrap();
+        topic.clone().encode(&order_placed()).await.unwrap();
+        assert_eq!(fake.get_topic_calls.load(Ordering::SeqCst), 1);
+
+        fake.publish_failures
+            .lock()
+            .unwrap()
+            .push_back(tonic::Status::unavailable("try again"));
+        assert!(topic.publish(&[order_placed()]).await.is_err());
+        fake.schemas
+            .lock()
+            .unwrap()
+            .insert("schema-2".to_string(), SCHEMA.to_string());
+        fake.topics
+            .lock()
+            .unwrap()
+            .get_mut(TOPIC)
+            .unwrap()
+            .schema_id = "schema-2".to_string();
+
+        topic.publish(&[order_placed()]).await.unwrap();
+        assert_eq!(fake.get_topic_calls.load(Ordering::SeqCst), 2);
+        let published = fake.published.lock().unwrap();
+        assert_eq!(published.last().unwrap().events[0].schema_id, "schema-2");
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_decodes_events() {
+        let order = OrderPlaced {
+            order_number: Some("ORD-1001".to_string()),
+            amount: None,
+        };
+        let (fake, topic) = topic::<OrderPlaced>(FakePubSub::default()).await;
+        let event = topic.encode(&order).await.unwrap();
+        let bad_event = ProducerEvent {
+            payload: vec![0xff],
+            ..event.clone()
+        };
+        fake.push_session(vec![Ok(FetchResponse {
+            events: vec![
+                ConsumerEvent {
+                    event: Some(event.clone()),
+                    replay_id: vec![1],
+                },
+                ConsumerEvent {
+                    event: Some(bad_event),
+                    replay_id: vec![2],
+                },
+            ],
+            ..Default::default()
+        })]);
+
+        let events: Vec<_> = topic
+            .subscribe(FetchRequest {
+                num_requested: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(events.len(), 2);
+        let first = events[0].as_ref().unwrap();
+        assert_eq!(first.payload, order);
+        assert_eq!(first.id, event.id);
+        assert_eq!(first.replay_id, vec![1]);
+        assert!(matches!(events[1], Err(Error::Schema(_))));
+        assert_eq!(fake.fetch_requests.lock().unwrap()[0].topic_name, TOPIC);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_deserialize_mismatch() {
+        let (fake, topic) = topic::<OrderPlaced>(FakePubSub::default()).await;
+        let event = topic
+            .encode(&OrderPlaced {
+                order_number: None,
+                amount: None,
+            })
+            .await
+            .unwrap();
+        fake.push_session(vec![Ok(FetchResponse {
+            events: vec![ConsumerEvent {
+                event: Some(event),
+                replay_id: vec![1],
+            }],
+            ..Default::default()
+        })]);
+
+        let unrelated = Topic::<Unrelated>::new(topic.cache.clone(), TOPIC);
+        let events: Vec<_> = unrelated
+            .subscribe(FetchRequest::default())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(
+            &events[0],
+            Err(Error::Deserialize { schema_id, .. }) if schema_id == "schema-1"
+        ));
+    }
+}
@@ -0,0 +1,567 @@
+use crate::client::{self, AuthFlow, Credentials};
+use crate::http::{self, HttpConfig};
+use crate::pubsub::context::{self, Context};
+use crate::token::provider::{self, TokenProvider};
+use crate::token::store::TokenStore;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Errors that can occur while loading profiles or connecting to an org.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Required builder parameter was not provided.
+    #[error("Missing required attribute: {}", _0)]
+    MissingRequiredAttribute(String),
+    /// Failed to read the profiles file from disk.
+    #[error("Failed to read profiles file at {path}: {source}")]
+    ReadProfiles {
+        /// Path to the profiles file that failed to read.
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// Failed to parse a TOML profiles file.
+    #[error("Failed to parse profiles TOML: {source}")]
+    ParseToml {
+        #[source]
+        source: toml::de::Error,
+    },
+    /// Failed to parse a JSON profiles file.
+    #[error("Failed to parse profiles JSON: {source}")]
+    ParseJson {
+        #[source]
+        source: serde_json::Error,
+    },
+    /// No profile exists for the alias.
+    #[error("Unknown profile: {alias}")]
+    UnknownProfile {
+        /// The alias that was looked up.
+        alias: String,
+    },
+    /// Connecting to the org failed.
+    #[error("Failed to connect profile {alias}: {source}")]
+    Connect {
+        /// The alias of the profile.
+        alias: String,
+        #[source]
+        source: client::Error,
+    },
+    /// Refreshing the org's access token failed.
+    #[error("Failed to refresh access token for profile {alias}: {source}")]
+    TokenRefresh {
+        /// The alias of the profile.
+        alias: String,
+        #[source]
+        source: provider::Error,
+    },
+    /// Opening the Pub/Sub channel failed.
+    #[error("Failed to open Pub/Sub channel for profile {alias}: {source}")]
+    Channel {
+        /// The alias of the profile.
+        alias: String,
+        #[source]
+        source: http::Error,
+    },
+    /// Creating the Pub/Sub context failed.
+    #[error("Failed to create Pub/Sub context for profile {alias}: {source}")]
+    Context {
+        /// The alias of the profile.
+        alias: String,
+        #[source]
+        source: context::Error,
+    },
+}
+
+/// Connection settings for one org.
+///
+/// The [`Credentials`] fields are flattened into the profile, next to the
+/// flow and the Pub/Sub endpoint.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Profile {
+    /// OAuth2 flow used to connect. Defaults to [`AuthFlow::ClientCredentials`].
+    #[serde(default)]
+    pub auth_flow: AuthFlow,
+    /// Pub/Sub API endpoint. Defaults to `https://api.pubsub.salesforce.com`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pubsub_endpoint: Option<String>,
+    /// Credentials for the org.
+    #[serde(flatten)]
+    pub credentials: Credentials,
+}
+
+/// Named org profiles, as stored in a profiles file.
+///
+/// # Examples
+///
+/// ```toml
+/// [profiles.prod]
+/// auth_flow = "jwt_bearer"
+/// client_id = "prod_client_id"
+/// username = "integration@example.com"
+/// private_key_path = "/etc/salesforce/prod.key"
+/// instance_url = "https://login.salesforce.com"
+///
+/// [profiles.uat]
+/// client_id = "uat_client_id"
+/// client_secret = "uat_client_secret"
+/// instance_url = "https://example--uat.sandbox.my.salesforce.com"
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Profiles {
+    /// Profiles keyed by alias.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Profiles {
+    /// Parses profiles from a TOML document.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ParseToml`] if the document is invalid.
+    pub fn from_toml(s: &str) -> Result<Self, Error> {
+        toml::from_str(s).map_err(|e| Error::ParseToml { source: e })
+    }
+
+    /// Parses profiles from a JSON document.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ParseJson`] if the document is invalid.
+    pub fn from_json(s: &str) -> Result<Self, Error> {
+        serde_json::from_str(s).map_err(|e| Error::ParseJson { source: e })
+    }
+}
+
+/// Lazily established connection to one org.
+#[derive(Debug)]
+struct Connection {
+    token_provider: TokenProvider,
+    channel: OnceCell<tonic::transport::Channel>,
+}
+
+/// Registry of org profiles that connects to each org on first use.
+///
+/// The first call for an alias connects with the profile's flow; later calls
+/// reuse the connection and its token, which is refreshed as it nears expiry.
+/// Concurrent first calls for the same alias connect only once.
+///
+/// # Examples
+///
+/// ```no_run
+/// use salesforce_core::registry;
+/// use std::path::PathBuf;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let registry = registry::Builder::new()
+///     .profiles_path(PathBuf::from("profiles.toml"))
+///     .build()?;
+///
+/// let client = registry.client("uat").await?;
+/// let mut context = registry.pubsub("uat").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct OrgRegistry {
+    profiles: BTreeMap<String, Profile>,
+    http_config: HttpConfig,
+    token_store: Option<Arc<dyn TokenStore>>,
+    connections: Mutex<HashMap<String, Arc<OnceCell<Arc<Connection>>>>>,
+}
+
+impl OrgRegistry {
+    /// Returns the aliases of all profiles in sorted order.
+    pub fn aliases(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    /// Returns the profile for `alias`.
+    pub fn profile(&self, alias: &str) -> Option<&Profile> {
+        self.profiles.get(alias)
+    }
+
+    /// Returns the shared token provider for `alias`, connecting if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnknownProfile`] if no profile exists for `alias`, or
+    /// [`Error::Connect`] if connecting fails.
+    pub async fn token_provider(&self, alias: &str) -> Result<TokenProvider, Error> {
+        Ok(self.connection(alias).await?.token_provider.clone())
+    }
+
+    /// Returns a connected client for `alias` with a fresh access token.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnknownProfile`] if no profile exists for `alias`,
+    /// [`Error::Connect`] if connecting fails, or [`Error::TokenRefresh`] if
+    /// the token cannot be refreshed.
+    pub async fn client(&self, alias: &str) -> Result<client::Client, Error> {
+        let token_provider = self.token_provider(alias).await?;
+        token_provider
+            .ensure_fresh()
+            .await
+            .map_err(|e| Error::TokenRefresh {
+                alias: alias.to_string(),
+                source: e,
+            })?;
+        Ok(token_provider.client().await)
+    }
+
+    /// Returns a Pub/Sub context for `alias`.
+    ///
+    /// Contexts for the same alias share one gRPC channel and token.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnknownProfile`] if no profile exists for `alias`,
+    /// [`Error::Connect`] if connecting fails, [`Error::Channel`] if the
+    /// channel cannot be configured, or [`Error::Context`] if the context
+    /// cannot be created.
+    pub async fn pubsub(&self, alias: &str) -> Result<Context, Error> {
+        let connection = self.connection(alias).await?;
+        let channel = connection
+            .channel
+            .get_or_try_init(|| async {
+                let endpoint = self.profiles[alias]
+                    .pubsub_endpoint
+                    .as_deref()
+                    .unwrap_or(salesforce_pubsub_v1::eventbus::ENDPOINT);
+                self.http_config
+                    .connect_channel_lazy(endpoint)
+                    .map_err(|e| Error::Channel {
+                        alias: alias.to_string(),
+                        source: e,
+                    })
+            })
+            .await?
+            .clone();
+
+        Context::with_token_provider(channel, connection.token_provider.clone())
+            .await
+            .map_err(|e| Error::Context {
+                alias: alias.to_string(),
+                source: e,
+            })
+    }
+
+    /// Returns the connection for `alias`, establishing it on first use.
+    async fn connection(&self, alias: &str) -> Result<Arc<Connection>, Error> {
+        let profile = self
+            .profiles
+            .get(alias)
+            .ok_or_else(|| Error::UnknownProfile {
+                alias: alias.to_string(),
+            })?;
+        let cell = self
+            .connections
+            .lock()
+            .unwrap()
+            .entry(alias.to_string())
+            .or_default()
+            .clone();
+
+        let connection = cell
+            .get_or_try_init(|| self.connect(alias, profile))
+            .await?;
+        Ok(connection.clone())
+    }
+
+    /// Connects to the org described by `profile`.
+    async fn connect(&self, alias: &str, profile: &Profile) -> Result<Arc<Connection>, Error> {
+        let connect_error = |e| Error::Connect {
+            alias: alias.to_string(),
+            source: e,
+        };
+
+        let mut builder = client::Builder::new()
+            .credentials(profile.credentials.clone())
+            .auth_flow(profile.auth_flow)
+            .http_config(self.http_config.clone());
+        if let Some(token_store) = &self.token_store {
+            builder = builder.token_store(token_store.clone());
+        }
+        let client = builder
+            .build()
+            .map_err(connect_error)?
+            .connect()
+            .await
+            .map_err(connect_error)?;
+        tracing::debug!("Connected profile {alias}");
+
+        let token_provider = TokenProvider::new(client).map_err(|e| Error::TokenRefresh {
+            alias: alias.to_string(),
+            source: e,
+        })?;
+        Ok(Arc::new(Connection {
+            token_provider,
+            channel: OnceCell::new(),
+        }))
+    }
+}
+
+/// Builder for creating [`OrgRegistry`] instances.
+///
+/// # Examples
+///
+/// ```no_run
+/// use salesforce_core::registry;
+/// use std::path::PathBuf;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let registry = registry::Builder::new()
+///     .profiles_path(PathBuf::from("profiles.json"))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct Builder {
+    profiles_path: Option<PathBuf>,
+    profiles: Option<Profiles>,
+    http_config: Option<HttpConfig>,
+    token_store: Option<Arc<dyn TokenStore>>,
+}
+
+impl Builder {
+    /// Creates a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the profiles file to load.
+    ///
+    /// Files with a `.toml` extension are parsed as TOML, all others as JSON.
+    pub fn profiles_path(mut self, path: PathBuf) -> Self {
+        self.profiles_path = Some(path);
+        self
+    }
+
+    /// Sets the profiles directly.
+    pub fn profiles(mut self, profiles: Profiles) -> Self {
+        self.profiles = Some(profiles);
+        self
+    }
+
+    /// Sets the network policy for OAuth2 requests and Pub/Sub channels.
+    pub fn http_config(mut self, http_config: HttpConfig) -> Self {
+        self.http_config = Some(http_config);
+        self
+    }
+
+    /// Sets the token store shared by the clients of all profiles.
+    pub fn token_store(mut self, token_store: Arc<dyn TokenStore>) -> Self {
+        self.token_store = Some(token_store);
+        self
+    }
+
+    /// Builds the registry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if neither [`profiles_path`](Self::profiles_path) nor
+    /// [`profiles`](Self::profiles) was set, or the profiles file cannot be
+    /// read or parsed.
+    pub fn build(self) -> Result<OrgRegistry, Error> {
+        let profiles = match (self.profiles, self.profiles_path) {
+            (Some(profiles), _) => profiles,
+            (None, Some(path)) => {
+                let contents = std::fs::read_to_string(&path).map_err(|e| Error::ReadProfiles {
+                    path: path.clone(),
+                    source: e,
+                })?;
+                if path
+                    .extension()
+                    .is_some_and(|extension| extension == "toml")
+                {
+                    Profiles::from_toml(&contents)?
+                } else {
+                    Profiles::from_json(&contents)?
+                }
+            }
+            (None, None) => {
+                return Err(Error::MissingRequiredAttribute(
+                    "profiles or profiles_path".to_string(),
+                ))
+            }
+        };
+
+        Ok(OrgRegistry {
+            profiles: profiles.profiles,
+            http_config: self.http_config.unwrap_or_default(),
+            token_store: self.token_store,
+            connections: Mutex::new(HashMap::new()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oauth2::TokenResponse as _;
+
+    const PROFILES_TOML: &str = r#"
+        [profiles.prod]
+        auth_flow = "jwt_bearer"
+        client_id = "prod_client_id"
+        username = "integration@example.com"
+        private_key_path = "/etc/salesforce/prod.key"
+        instance_url = "https://login.salesforce.com"
+
+        [profiles.uat]
+        client_id = "uat_client_id"
+        client_secret = "uat_client_secret"
+        instance_url = "https://example--uat.sandbox.my.salesforce.com"
+        pubsub_endpoint = "https://api.deu.pubsub.salesforce.com"
+    "#;
+
+    fn registry(instance_url: &str) -> OrgRegistry {
+        let profiles = Profiles::from_json(&format!(
+            r#"{{"profiles": {{"uat": {{
+                "client_id": "uat_client_id",
+                "client_secret": "uat_client_secret",
+                "instance_url": "{instance_url}",
+                "pubsub_endpoint": "http://localhost:50051"
+            }}}}}}"#
+        ))
+        .unwrap();
+        Builder::new().profiles(profiles).build().unwrap()
+    }
+
+    async fn token_mock(server: &wiremock::MockServer, expected_calls: u64) {
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .and(wiremock::matchers::path("/services/oauth2/token"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "access_token": "uat_token",
+                    "token_type": "Bearer",
+                    "id": "https://login.salesforce.com/id/00Dxx0000001gPLEAY/005xx000001SwiUAAS"
+                })),
+            )
+            .expect(expected_calls)
+            .mount(server)
+            .await;
+    }
+
+    #[test]
+    fn test_profiles_from_toml() {
+        let profiles = Profiles::from_toml(PROFILES_TOML).unwrap();
+        let prod = &profiles.profiles["prod"];
+        assert_eq!(prod.auth_flow, AuthFlow::JwtBearer);
+        assert_eq!(
+            prod.credentials.username.as_deref(),
+            Some("integration@example.com")
+        );
+        let uat = &profiles.profiles["uat"];
+        assert_eq!(uat.auth_flow, AuthFlow::ClientCredentials);
+        assert_eq!(
+            uat.credentials.client_secret,
+            Some("uat_client_secret".into())
+        );
+        assert_eq!(
+            uat.pubsub_endpoint.as_deref(),
+            Some("https://api.deu.pubsub.salesforce.com")
+        );
+    }
+
+    #[test]
+    fn test_profiles_invalid() {
+        assert!(matches!(
+            Profiles::from_toml("[profiles.prod]\nclient_id = 1"),
+            Err(Error::ParseToml { .. })
+        ));
+        assert!(matches!(
+            Profiles::from_json("{"),
+            Err(Error::ParseJson { .. })
+        ));
+    }
+
+    #[test]
+    fn test_build_from_toml_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("profiles.toml");
+        std::fs::write(&path, PROFILES_TOML).unwrap();
+
+        let registry = Builder::new().profiles_path(path).build().unwrap();
+        assert_eq!(registry.aliases().collect::<Vec<_>>(), vec!["prod", "uat"]);
+        assert!(registry.profile("prod").is_some());
+        assert!(registry.profile("dev").is_none());
+    }
+
+    #[test]
+    fn test_build_without_profiles() {
+        assert!(matches!(
+            Builder::new().build(),
+            Err(Error::MissingRequiredAttribute(_))
+        ));
+        assert!(matches!(
+            Builder::new()
+                .profiles_path(PathBuf::from("/nonexistent/profiles.toml"))
+                .build(),
+            Err(Error::ReadProfiles { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_profile() {
+        let registry = registry("https://test.salesforce.com");
+        assert!(matches!(
+            registry.client("dev").await,
+            Err(Error::UnknownProfile { alias }) if alias == "dev"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_client_connects_once() {
+        let server = wiremock::MockServer::start().await;
+        token_mock(&server, 1).await;
+        let registry = registry(&server.uri());
+
+        let (first, second) = tokio::join!(registry.client("uat"), registry.client("uat"));
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!(
+            first.token_result.unwrap().access_token().secret(),
+            "uat_token"
+        );
+        assert_eq!(second.tenant_id.as_deref(), Some("00Dxx0000001gPLEAY"));
+        registry.client("uat").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connect_error() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(400)
+                    .set_body_json(serde_json::json!({"error": "invalid_client"})),
+            )
+            .mount(&server)
+            .await;
+        let registry = registry(&server.uri());
+
+        let result = registry.client("uat").await;
+        assert!(matches!(
+            result,
+            Err(Error::Connect { alias, .. }) if alias == "uat"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pubsub_shares_token_provider() {
+        let server = wiremock::MockServer::start().await;
+        token_mock(&server, 1).await;
+        let registry = registry(&server.uri());
+
+        let first = registry.pubsub("uat").await.unwrap();
+        let second = registry.pubsub("uat").await.unwrap();
+        assert_eq!(first.token_provider().access_token(), "uat_token");
+        assert_eq!(second.token_provider().access_token(), "uat_token");
+    }
+}
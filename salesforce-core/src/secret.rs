@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+/// Marker printed in place of a secret value.
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+/// String that is redacted in `Debug` and `Display` output.
+///
+/// Serializes and deserializes as a plain string, so credentials files keep
+/// their format. Use [`expose_secret`](Self::expose_secret) to read the value.
+///
+/// # Examples
+///
+/// ```
+/// use salesforce_core::secret::SecretString;
+///
+/// let secret = SecretString::from("hunter2");
+/// assert_eq!(format!("{secret:?}"), "[REDACTED]");
+/// assert_eq!(secret.to_string(), "[REDACTED]");
+/// assert_eq!(secret.expose_secret(), "hunter2");
+/// assert_eq!(serde_json::to_string(&secret).unwrap(), "\"hunter2\"");
+/// ```
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretString(String);
+
+impl SecretString {
+    /// Wraps a secret value.
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    /// Returns the secret value.
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl std::fmt::Display for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_and_display_redact() {
+        let secret = SecretString::new("s3cr3t");
+        assert!(!format!("{secret:?}").contains("s3cr3t"));
+        assert!(!format!("{secret}").contains("s3cr3t"));
+        assert!(!format!("{:?}", Some(secret)).contains("s3cr3t"));
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let secret: SecretString = serde_json::from_str("\"s3cr3t\"").unwrap();
+        assert_eq!(secret.expose_secret(), "s3cr3t");
+        assert_eq!(serde_json::to_string(&secret).unwrap(), "\"s3cr3t\"");
+    }
+}
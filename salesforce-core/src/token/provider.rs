@@ -0,0 +1,492 @@
+use crate::client;
+use oauth2::TokenResponse;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Mutex;
+
+/// Default time before expiry at which an access token is refreshed.
+const DEFAULT_REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// Delay before retrying a failed background refresh.
+const REFRESH_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Errors that can occur while providing access tokens.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// OAuth2 token is missing from client.
+    #[error("Token response missing")]
+    MissingTokenResponse(),
+    /// Refreshing the access token failed.
+    #[error("Failed to refresh access token: {source}")]
+    Refresh {
+        #[source]
+        source: client::Error,
+    },
+}
+
+impl Error {
+    /// Returns whether refreshing may succeed if retried.
+    ///
+    /// See [`client::Error::is_retryable`].
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::MissingTokenResponse() => false,
+            Error::Refresh { source } => source.is_retryable(),
+        }
+    }
+}
+
+/// Snapshot of the current access token.
+#[derive(Clone)]
+struct TokenState {
+    access_token: String,
+    expires_at: SystemTime,
+}
+
+struct Inner {
+    client: Mutex<client::Client>,
+    state: RwLock<TokenState>,
+    /// How long before expiry the token is due for refresh.
+    refresh_margin: RwLock<Duration>,
+    /// Closed when the last provider is dropped, stopping refresh tasks.
+    shutdown: tokio::sync::watch::Sender<()>,
+}
+
+/// Shared access token that refreshes itself before it expires.
+///
+/// Wraps a connected [`client::Client`] and tracks the expiry of its token.
+/// The current token can be read synchronously from any clone, which makes
+/// the provider usable from gRPC interceptors. Refreshing uses the refresh
+/// token when the server issued one, and otherwise re-runs the configured
+/// [`client::AuthFlow`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use salesforce_core::client;
+/// use salesforce_core::token::provider::TokenProvider;
+/// use std::path::PathBuf;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = client::Builder::new()
+///     .credentials_path(PathBuf::from("credentials.json"))
+///     .build()?
+///     .connect()
+///     .await?;
+///
+/// let provider = TokenProvider::new(client)?;
+/// // Keep the token fresh in the background for long-running processes.
+/// let _refresh_task = provider.spawn_refresh();
+///
+/// let access_token = provider.access_token();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct TokenProvider {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for TokenProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenProvider")
+            .field("expires_at", &self.expires_at())
+            .field("refresh_margin", &self.margin())
+            .finish_non_exhaustive()
+    }
+}
+
+impl TokenProvider {
+    /// Creates a provider from a connected client.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MissingTokenResponse`] if the client has not been connected.
+    pub fn new(client: client::Client) -> Result<Self, Error> {
+        let state = token_state(&client)?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                client: Mutex::new(client),
+                state: RwLock::new(state),
+                refresh_margin: RwLock::new(DEFAULT_REFRESH_MARGIN),
+                shutdown: tokio::sync::watch::channel(()).0,
+            }),
+        })
+    }
+
+    /// Sets how long before expiry the token is considered due for refresh.
+    ///
+    /// Defaults to five minutes. The margin is shared by all clones of the
+    /// provider, including those made earlier.
+    pub fn refresh_margin(self, refresh_margin: Duration) -> Self {
+        *self
+            .inner
+            .refresh_margin
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = refresh_margin;
+        self
+    }
+
+    /// Returns the current access token.
+    pub fn access_token(&self) -> String {
+        self.state().access_token
+    }
+
+    /// Returns the point in time at which the current access token expires.
+    pub fn expires_at(&self) -> SystemTime {
+        self.state().expires_at
+    }
+
+    /// Returns `true` if the token expires within the refresh margin.
+    pub fn needs_refresh(&self) -> bool {
+        SystemTime::now()
+            .checked_add(self.margin())
+            .is_none_or(|due| due >= self.expires_at())
+    }
+
+    /// Returns a copy of the underlying client with the current token.
+    pub async fn client(&self) -> client::Client {
+        self.inner.client.lock().await.clone()
+    }
+
+    /// Refreshes the access token unconditionally.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Refresh`] if the token exchange fails. The previous
+    /// token stays in place in that case.
+    pub async fn refresh(&self) -> Result<(), Error> {
+        let mut client = self.inner.client.lock().await;
+        self.refresh_locked(&mut client).await
+    }
+
+    /// Refreshes the access token if it expires within the refresh margin.
+    ///
+    /// Concurrent callers share a single refresh.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Refresh`] if the token exchange fails.
+    pub async fn ensure_fresh(&self) -> Result<(), Error> {
+        if !self.needs_refresh() {
+            return Ok(());
+        }
+        let mut client = self.inner.client.lock().await;
+        // Another caller may have refreshed while we waited for the lock.
+        if !self.needs_refresh() {
+            return Ok(());
+        }
+        self.refresh_locked(&mut client).await
+    }
+
+    /// Spawns a task that refreshes the token shortly before each expiry.
+    ///
+    /// The task stops once every clone of the provider has been dropped.
+    /// Failed refreshes are logged and retried after a short delay.
+    pub fn spawn_refresh(&self) -> tokio::task::JoinHandle<()> {
+        let inner = Arc::downgrade(&self.inner);
+        let mut shutdown = self.inner.shutdown.subscribe();
+
+        tokio::spawn(async move {
+            let upgrade = || inner.upgrade().map(|inner| TokenProvider { inner });
+            let mut delay = match upgrade() {
+                Some(provider) => provider.refresh_delay(),
+                None => return,
+            };
+
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = shutdown.changed() => return,
+                }
+
+                let Some(provider) = upgrade() else {
+                    return;
+                };
+                delay = match provider.ensure_fresh().await {
+                    Ok(()) => provider.refresh_delay(),
+                    Err(e) => {
+                        tracing::warn!("Background token refresh failed: {e}");
+                        REFRESH_RETRY_DELAY
+                    }
+                };
+            }
+        })
+    }
+
+    /// Time left until the token is due for refresh.
+    fn refresh_delay(&self) -> Duration {
+        self.expires_at()
+            .checked_sub(self.margin())
+            .and_then(|due| due.duration_since(SystemTime::now()).ok())
+            .unwrap_or_default()
+    }
+
+    fn margin(&self) -> Duration {
+        *self
+            .inner
+            .refresh_margin
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn state(&self) -> TokenState {
+        self.inner
+            .state
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    async fn refresh_locked(&self, client: &mut client::Client) -> Result<(), Error> {
+        client
+            .refresh()
+            .await
+            .map_err(|e| Error::Refresh { source: e })?;
+        let state = token_state(client)?;
+        *self.inner.state.write().unwrap_or_else(|e| e.into_inner()) = state;
+        tracing::debug!("Access token refreshed");
+        Ok(())
+    }
+}
+
+fn token_state(client: &client::Client) -> Result<TokenState, Error> {
+    let access_token = client
+        .token_result
+        .as_ref()
+        .ok_or_else(Error::MissingTokenResponse)?
+        .access_token()
+        .secret()
+        .clone();
+    let expires_at = client
+        .expires_at()
+        .ok_or_else(Error::MissingTokenResponse)?;
+    Ok(TokenState {
+        access_token,
+        expires_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use oauth2::AccessToken;
+
+    fn token(access_token: &str) -> client::TokenResponse {
+        client::TokenResponse::new(
+            AccessToken::new(access_token.to_string()),
+            oauth2::basic::BasicTokenType::Bearer,
+            Default::default(),
+        )
+    }
+
+    fn mock_token_response(access_token: &str) -> wiremock::ResponseTemplate {
+        wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "access_token": access_token,
+            "token_type": "Bearer",
+            "refresh_token": "refresh_token_1"
+        }))
+    }
+
+    async fn connected_client(server: &wiremock::MockServer) -> client::Client {
+        client::Builder::new()
+            .credentials(client::Credentials {
+                client_id: "test_client_id".to_string(),
+                client_secret: Some("test_secret".into()),
+                instance_url: server.uri(),
+                tenant_id: Some("test_tenant".to_string()),
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+            .connect()
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_new_missing_token() {
+        let client = client::Builder::new()
+            .credentials_path("/tmp/test.json".into())
+            .build()
+            .unwrap();
+        let result = TokenProvider::new(client);
+        assert!(matches!(result, Err(Error::MissingTokenResponse())));
+    }
+
+    #[test]
+    fn test_access_token_and_expiry() {
+        let mut client = client::Builder::new()
+            .credentials_path("/tmp/test.json".into())
+            .token_lifetime(Duration::from_secs(3600))
+            .build()
+            .unwrap();
+        client.token_result = Some(token("test_token"));
+
+        let provider = TokenProvider::new(client).unwrap();
+        assert_eq!(provider.access_token(), "test_token");
+        assert!(provider.expires_at() > SystemTime::now() + Duration::from_secs(3500));
+        assert!(!provider.needs_refresh());
+
+        let provider = provider.refresh_margin(Duration::from_secs(7200));
+        assert!(provider.needs_refresh());
+    }
+
+    #[test]
+    fn test_refresh_margin_is_shared_by_clones() {
+        let mut client = client::Builder::new()
+            .credentials_path("/tmp/test.json".into())
+            .token_lifetime(Duration::from_secs(3600))
+            .build()
+            .unwrap();
+        client.token_result = Some(token("test_token"));
+
+        let provider = TokenProvider::new(client).unwrap();
+        let earlier_clone = provider.clone();
+        let provider = provider.refresh_margin(Duration::MAX);
+        assert!(earlier_clone.needs_refresh());
+        assert!(provider.needs_refresh());
+        assert_eq!(provider.refresh_delay(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_debug_hides_token() {
+        let mut client = client::Builder::new()
+            .credentials_path("/tmp/test.json".into())
+            .build()
+            .unwrap();
+        client.token_result = Some(token("secret_token"));
+
+        let provider = TokenProvider::new(client).unwrap();
+        let debug_str = format!("{provider:?}");
+        assert!(debug_str.contains("TokenProvider"));
+        assert!(!debug_str.contains("secret_token"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_uses_refresh_token() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .and(wiremock::matchers::body_string_contains(
+                "grant_type=client_credentials",
+            ))
+            .respond_with(mock_token_response("token_1"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .and(wiremock::matchers::body_string_contains(
+                "grant_type=refresh_token",
+            ))
+            .and(wiremock::matchers::body_string_contains(
+                "refresh_token=refresh_token_1",
+            ))
+            .respond_with(wiremock::ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"access_token": "token_2", "token_type": "Bearer"}),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = TokenProvider::new(connected_client(&server).await).unwrap();
+        assert_eq!(provider.access_token(), "token_1");
+
+        provider.refresh().await.unwrap();
+        assert_eq!(provider.access_token(), "token_2");
+
+        // The refresh token survives a response that doesn't rotate it.
+        let client = provider.client().await;
+        assert_eq!(
+            client
+                .token_result
+                .unwrap()
+                .refresh_token()
+                .unwrap()
+                .secret(),
+            "refresh_token_1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ensure_fresh_reruns_flow_before_expiry() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "access_token": "token_1",
+                    "token_type": "Bearer",
+                    "expires_in": 60
+                })),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "access_token": "token_2",
+                    "token_type": "Bearer",
+                    "expires_in": 3600
+                })),
+            )
+            .mount(&server)
+            .await;
+
+        let provider = TokenProvider::new(connected_client(&server).await).unwrap();
+        assert_eq!(provider.access_token(), "token_1");
+        assert!(provider.needs_refresh());
+
+        let clone = provider.clone();
+        provider.ensure_fresh().await.unwrap();
+        assert_eq!(clone.access_token(), "token_2");
+        assert!(!clone.needs_refresh());
+
+        // Token is fresh now, so no further exchange happens.
+        clone.ensure_fresh().await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_failure_keeps_previous_token() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(wiremock::ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"access_token": "token_1", "token_type": "Bearer"}),
+            ))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(400)
+                    .set_body_json(serde_json::json!({"error": "invalid_grant"})),
+            )
+            .mount(&server)
+            .await;
+
+        let provider = TokenProvider::new(connected_client(&server).await).unwrap();
+        let result = provider.refresh().await;
+        assert!(matches!(result, Err(Error::Refresh { .. })));
+        assert_eq!(provider.access_token(), "token_1");
+    }
+
+    #[tokio::test]
+    async fn test_spawn_refresh_stops_when_dropped() {
+        let mut client = client::Builder::new()
+            .credentials_path("/tmp/test.json".into())
+            .build()
+            .unwrap();
+        client.token_result = Some(token("test_token"));
+
+        let provider = TokenProvider::new(client).unwrap();
+        let handle = provider.spawn_refresh();
+        drop(provider);
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
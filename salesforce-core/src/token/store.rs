@@ -0,0 +1,471 @@
+use crate::atomic_file;
+use crate::client::{Credentials, TokenResponse};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Header identifying the encrypted token store file format.
+const FILE_MAGIC: &[u8; 4] = b"SFT1";
+
+/// Length of the AES-GCM nonce stored after the header.
+const NONCE_LEN: usize = 12;
+
+/// Errors that can occur while reading or writing cached tokens.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Failed to read the token store file from disk.
+    #[error("Failed to read token store at {path}: {source}")]
+    Read {
+        /// Path to the token store file.
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// Failed to write the token store file to disk.
+    #[error("Failed to write token store at {path}: {source}")]
+    Write {
+        /// Path to the token store file.
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// Failed to serialize or deserialize cached tokens.
+    #[error("Failed to serialize cached tokens: {source}")]
+    Serialize {
+        #[source]
+        source: serde_json::Error,
+    },
+    /// The token store file could not be decrypted.
+    ///
+    /// The key is wrong or the file is corrupted.
+    #[error("Failed to decrypt token store at {path}")]
+    Decrypt {
+        /// Path to the token store file.
+        path: PathBuf,
+    },
+    /// Cached tokens could not be encrypted.
+    #[error("Failed to encrypt cached tokens")]
+    Encrypt,
+}
+
+/// Identifies the session a cached token belongs to.
+///
+/// Tokens are shared between clients that use the same connected app, user
+/// and login host.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TokenKey {
+    /// OAuth2 client ID of the connected app.
+    pub client_id: String,
+    /// Salesforce username, if the flow authenticates a specific user.
+    pub username: Option<String>,
+    /// Login host the token was requested from.
+    pub instance_url: String,
+}
+
+impl TokenKey {
+    /// Builds the key for tokens obtained with the given credentials.
+    pub fn new(credentials: &Credentials) -> Self {
+        Self {
+            client_id: credentials.client_id.clone(),
+            username: credentials.username.clone(),
+            instance_url: credentials.instance_url.clone(),
+        }
+    }
+}
+
+/// Token response saved in a [`TokenStore`] together with its expiry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedToken {
+    /// Token response returned by the authorization server.
+    pub token_result: TokenResponse,
+    /// Time at which the access token expires.
+    pub expires_at: SystemTime,
+}
+
+impl CachedToken {
+    /// Returns `true` if the token stays valid for at least `min_remaining`.
+    pub fn is_valid_for(&self, min_remaining: Duration) -> bool {
+        self.expires_at
+            .duration_since(SystemTime::now())
+            .is_ok_and(|remaining| remaining >= min_remaining)
+    }
+}
+
+/// Persists access tokens so they can be reused across clients and processes.
+///
+/// [`Client::connect`](crate::client::Client::connect) consults the store
+/// before running the configured [`AuthFlow`](crate::client::AuthFlow), and
+/// saves every token it obtains.
+///
+/// Implementations may block: the client calls them from Tokio's blocking
+/// thread pool.
+pub trait TokenStore: std::fmt::Debug + Send + Sync {
+    /// Returns the token cached for `key`, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backing storage cannot be read.
+    fn load(&self, key: &TokenKey) -> Result<Option<CachedToken>, Error>;
+
+    /// Saves `token` for `key`, replacing any previous token.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backing storage cannot be written.
+    fn save(&self, key: &TokenKey, token: &CachedToken) -> Result<(), Error>;
+
+    /// Removes the token cached for `key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backing storage cannot be written.
+    fn remove(&self, key: &TokenKey) -> Result<(), Error>;
+}
+
+/// Token store that keeps tokens in memory.
+///
+/// Share one store between clients in the same process with an
+/// [`Arc`](std::sync::Arc).
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    tokens: Mutex<HashMap<TokenKey, CachedToken>>,
+}
+
+impl MemoryTokenStore {
+    /// Creates an empty in-memory token store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load(&self, key: &TokenKey) -> Result<Option<CachedToken>, Error> {
+        Ok(self
+            .tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(key)
+            .cloned())
+    }
+
+    fn save(&self, key: &TokenKey, token: &CachedToken) -> Result<(), Error> {
+        self.tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key.clone(), token.clone());
+        Ok(())
+    }
+
+    fn remove(&self, key: &TokenKey) -> Result<(), Error> {
+        self.tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(key);
+        Ok(())
+    }
+}
+
+/// Entry of the encrypted token store file.
+#[derive(Serialize, Deserialize)]
+struct FileEntry {
+    key: TokenKey,
+    token: CachedToken,
+}
+
+/// Token store that keeps tokens in an AES-256-GCM encrypted file.
+///
+/// The file is replaced atomically on every write, so processes sharing it
+/// never read a partially written file. Concurrent writers follow a
+/// last-writer-wins policy.
+///
+/// # Examples
+///
+/// ```no_run
+/// use salesforce_core::client;
+/// use salesforce_core::token::store::FileTokenStore;
+/// use std::path::PathBuf;
+/// use std::sync::Arc;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// // Load the key from a secret manager; keep it stable across runs.
+/// let key = FileTokenStore::generate_key();
+/// let store = FileTokenStore::new(PathBuf::from("tokens.bin"), key);
+///
+/// let client = client::Builder::new()
+///     .credentials_path(PathBuf::from("credentials.json"))
+///     .token_store(Arc::new(store))
+///     .build()?
+///     .connect()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct FileTokenStore {
+    path: PathBuf,
+    cipher: Aes256Gcm,
+    /// Serializes read-modify-write cycles within the process.
+    lock: Mutex<()>,
+}
+
+impl std::fmt::Debug for FileTokenStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileTokenStore")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FileTokenStore {
+    /// Creates a store backed by the file at `path`, encrypted with `key`.
+    ///
+    /// The file is created on the first save.
+    pub fn new(path: PathBuf, key: [u8; 32]) -> Self {
+        Self {
+            path,
+            cipher: Aes256Gcm::new(&Key::<Aes256Gcm>::from(key)),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Generates a random encryption key.
+    pub fn generate_key() -> [u8; 32] {
+        Aes256Gcm::generate_key(OsRng).into()
+    }
+
+    /// Returns the path of the backing file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads and decrypts all entries, treating a missing file as empty.
+    fn read_entries(&self) -> Result<Vec<FileEntry>, Error> {
+        let contents = match fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(Error::Read {
+                    path: self.path.clone(),
+                    source: e,
+                });
+            }
+        };
+
+        let decrypt_error = || Error::Decrypt {
+            path: self.path.clone(),
+        };
+        let body = contents
+            .strip_prefix(FILE_MAGIC.as_slice())
+            .filter(|body| body.len() >= NONCE_LEN)
+            .ok_or_else(decrypt_error)?;
+        let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+        let nonce: [u8; NONCE_LEN] = nonce.try_into().map_err(|_| decrypt_error())?;
+        let plaintext = self
+            .cipher
+            .decrypt(&Nonce::from(nonce), ciphertext)
+            .map_err(|_| decrypt_error())?;
+
+        serde_json::from_slice(&plaintext).map_err(|e| Error::Serialize { source: e })
+    }
+
+    /// Encrypts and atomically writes all entries.
+    fn write_entries(&self, entries: &[FileEntry]) -> Result<(), Error> {
+        let plaintext = serde_json::to_vec(entries).map_err(|e| Error::Serialize { source: e })?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| Error::Encrypt)?;
+
+        let mut contents = Vec::with_capacity(FILE_MAGIC.len() + NONCE_LEN + ciphertext.len());
+        contents.extend_from_slice(FILE_MAGIC);
+        contents.extend_from_slice(&nonce);
+        contents.extend_from_slice(&ciphertext);
+
+        atomic_file::write_private(&self.path, &contents).map_err(|e| Error::Write {
+            path: self.path.clone(),
+            source: e,
+        })
+    }
+
+    /// Applies `update` to the stored entries and writes them back.
+    fn update(&self, update: impl FnOnce(&mut Vec<FileEntry>)) -> Result<(), Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entries = self.read_entries()?;
+        update(&mut entries);
+        self.write_entries(&entries)
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self, key: &TokenKey) -> Result<Option<CachedToken>, Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(self
+            .read_entries()?
+            .into_iter()
+            .find(|entry| entry.key == *key)
+            .map(|entry| entry.token))
+    }
+
+    fn save(&self, key: &TokenKey, token: &CachedToken) -> Result<(), Error> {
+        self.update(|entries| {
+            entries.retain(|entry| entry.key != *key);
+            entries.push(FileEntry {
+                key: key.clone(),
+                token: token.clone(),
+            });
+        })
+    }
+
+    fn remove(&self, key: &TokenKey) -> Result<(), Error> {
+        self.update(|entries| entries.retain(|entry| entry.key != *key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oauth2::TokenResponse as _;
+
+    fn key(username: Option<&str>) -> TokenKey {
+        TokenKey {
+            client_id: "test_client_id".to_string(),
+            username: username.map(str::to_string),
+            instance_url: "https://login.salesforce.com".to_string(),
+        }
+    }
+
+    fn cached(access_token: &str, expires_in: Duration) -> CachedToken {
+        CachedToken {
+            token_result: TokenResponse::new(
+                oauth2::AccessToken::new(access_token.to_string()),
+                oauth2::basic::BasicTokenType::Bearer,
+                Default::default(),
+            ),
+            expires_at: SystemTime::now() + expires_in,
+        }
+    }
+
+    #[test]
+    fn test_token_key_from_credentials() {
+        let credentials = Credentials {
+            client_id: "test_client_id".to_string(),
+            username: Some("user@example.com".to_string()),
+            instance_url: "https://login.salesforce.com".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(TokenKey::new(&credentials), key(Some("user@example.com")));
+    }
+
+    #[test]
+    fn test_cached_token_is_valid_for() {
+        let token = cached("token", Duration::from_secs(600));
+        assert!(token.is_valid_for(Duration::from_secs(60)));
+        assert!(!token.is_valid_for(Duration::from_secs(900)));
+
+        let expired = CachedToken {
+            expires_at: SystemTime::now() - Duration::from_secs(1),
+            ..token
+        };
+        assert!(!expired.is_valid_for(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_memory_store_round_trip() {
+        let store = MemoryTokenStore::new();
+        assert!(store.load(&key(None)).unwrap().is_none());
+
+        store
+            .save(&key(None), &cached("token", Duration::from_secs(600)))
+            .unwrap();
+        let loaded = store.load(&key(None)).unwrap().unwrap();
+        assert_eq!(loaded.token_result.access_token().secret(), "token");
+        assert!(store
+            .load(&key(Some("other@example.com")))
+            .unwrap()
+            .is_none());
+
+        store.remove(&key(None)).unwrap();
+        assert!(store.load(&key(None)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_file_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tokens.bin");
+        let store_key = FileTokenStore::generate_key();
+        let store = FileTokenStore::new(path.clone(), store_key);
+        assert!(store.load(&key(None)).unwrap().is_none());
+
+        let token = cached("token", Duration::from_secs(600));
+        store.save(&key(None), &token).unwrap();
+        store
+            .save(
+                &key(Some("user@example.com")),
+                &cached("user_token", Duration::from_secs(600)),
+            )
+            .unwrap();
+        let replaced = cached("replaced", Duration::from_secs(600));
+        store.save(&key(None), &replaced).unwrap();
+
+        // A second store with the same key reads what the first one wrote.
+        let reopened = FileTokenStore::new(path.clone(), store_key);
+        let loaded = reopened.load(&key(None)).unwrap().unwrap();
+        assert_eq!(loaded.token_result.access_token().secret(), "replaced");
+        assert_eq!(loaded.expires_at, replaced.expires_at);
+
+        // The file does not contain the token in plain text.
+        let contents = fs::read(&path).unwrap();
+        assert!(!contents.windows(8).any(|window| window == b"replaced"));
+
+        reopened.remove(&key(None)).unwrap();
+        assert!(store.load(&key(None)).unwrap().is_none());
+        assert!(store
+            .load(&key(Some("user@example.com")))
+            .unwrap()
+            .is_some());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_store_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tokens.bin");
+        let store = FileTokenStore::new(path.clone(), FileTokenStore::generate_key());
+        store
+            .save(&key(None), &cached("token", Duration::from_secs(600)))
+            .unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_file_store_wrong_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tokens.bin");
+        FileTokenStore::new(path.clone(), FileTokenStore::generate_key())
+            .save(&key(None), &cached("token", Duration::from_secs(600)))
+            .unwrap();
+
+        let store = FileTokenStore::new(path, FileTokenStore::generate_key());
+        let result = store.load(&key(None));
+        assert!(matches!(result, Err(Error::Decrypt { .. })));
+    }
+
+    #[test]
+    fn test_file_store_debug_hides_key() {
+        let store = FileTokenStore::new(PathBuf::from("/tmp/tokens.bin"), [7; 32]);
+        let debug = format!("{store:?}");
+        assert!(debug.contains("tokens.bin"));
+        assert!(!debug.contains("cipher"));
+    }
+}